@@ -0,0 +1,78 @@
+//! Decoding speed of HCA audio, on tracks as long as the audio of a cutscene
+//!
+//! Run with `cargo bench --bench hca`
+
+use std::{
+    io::{Cursor, sink},
+    time::{Duration, Instant}
+};
+
+use gi_cutscenes_rs::filetypes::{HCAEncoder, HCAEncoderOptions, HCAFile};
+
+const SAMPLING_RATE: u32 = 48000;
+const SECONDS: u32 = 180;
+const RUNS: u32 = 5;
+
+/// A 16-bit stereo WAV file of chords and noise, reaching every band of the spectrum
+fn cutscene_wav() -> Vec<u8> {
+    let frames = SAMPLING_RATE * SECONDS;
+    let data_size = frames * 4;
+    let mut wav = Vec::with_capacity(data_size as usize + 44);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(data_size + 36).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16_u32.to_le_bytes());
+    wav.extend_from_slice(&1_u16.to_le_bytes());
+    wav.extend_from_slice(&2_u16.to_le_bytes());
+    wav.extend_from_slice(&SAMPLING_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLING_RATE * 4).to_le_bytes());
+    wav.extend_from_slice(&4_u16.to_le_bytes());
+    wav.extend_from_slice(&16_u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+
+    let mut noise: u32 = 0x1234_5678;
+    for frame in 0..frames {
+        let t = f64::from(frame) / f64::from(SAMPLING_RATE);
+        for (channel, base) in [220.0, 330.0].into_iter().enumerate() {
+            noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let hiss = f64::from(noise >> 16) / f64::from(u16::MAX) - 0.5;
+            let chord: f64 = (1..=4).map(|k| (std::f64::consts::TAU * base * f64::from(k) * 1.25 * t).sin() / f64::from(k)).sum();
+            let sample = 0.25f64.mul_add(chord, 0.05 * hiss) * (0.5 + 0.5 * (t * 0.7 + channel as f64).sin());
+            wav.extend_from_slice(&((sample * 24000.0) as i16).to_le_bytes());
+        }
+    }
+    wav
+}
+
+/// Fastest of `RUNS` runs of `run`
+fn fastest(mut run: impl FnMut()) -> Duration {
+    (0..RUNS).map(|_| {
+        let start = Instant::now();
+        run();
+        start.elapsed()
+    }).min().unwrap_or_default()
+}
+
+fn report(name: &str, time: Duration) {
+    println!("{name:<32} {:>8.1} ms  {:>6.0}x real time", time.as_secs_f64() * 1000.0, f64::from(SECONDS) / time.as_secs_f64());
+}
+
+fn main() {
+    let mut hca = Vec::new();
+    HCAEncoder::new(HCAEncoderOptions::default())
+        .and_then(|encoder| encoder.encode(Cursor::new(cutscene_wav()), &mut hca))
+        .expect("encoding the benchmark track");
+    println!("{SECONDS} s of 48 kHz stereo audio, {} kB of HCA", hca.len() / 1000);
+
+    let decode = || HCAFile::from_reader(&mut Cursor::new(&hca), [0; 4], [0; 4]).expect("reading the benchmark track");
+    report("decode to memory", fastest(|| {
+        decode().write_wav(&mut sink()).expect("decoding the benchmark track");
+    }));
+
+    let path = std::env::temp_dir().join("gi-cutscenes-rs-bench.wav");
+    report("decode to a WAV file", fastest(|| {
+        decode().convert_to_wav(&path).expect("decoding the benchmark track");
+    }));
+    std::fs::remove_file(&path).ok();
+}
@@ -1,6 +1,17 @@
-use std::path::{
-    Path,
-    PathBuf
+//! Demuxing pipelines, from USM files down to WAV/IVF streams and MKV containers
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{
+        BufWriter,
+        Cursor,
+        Write
+    },
+    path::{
+        Path,
+        PathBuf
+    }
 };
 
 use crate::{
@@ -9,18 +20,311 @@ use crate::{
         GICSResult
     },
     filetypes::{
+        ADXFile,
+        ADXWavDecoder,
+        ASSStyle,
+        AudioLanguages,
+        AudioInfo,
+        BlockErrors,
+        DamagedBlocks,
         HCAFile,
+        HCAWavDecoder,
+        KeyCheck,
+        KeyRecovery,
+        PCMFormat,
+        PCMOptions,
+        KEY_CHECK_BLOCKS,
+        SubtitleSink,
+        SubtitleTrack,
+        USMChunk,
         USMFile,
-        MKVFile
+        VideoInfo,
+        MKVFile,
+        MergeTrack
     },
     version::{
+        audio_encrypted,
+        blk_key,
+        candidate_keys,
+        KeyCandidate,
         definite_version_keys,
         Data
     }
 };
 
+/// Identifies one elementary stream of a demuxed file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StreamId {
+    /// Video stream, with its channel number
+    Video(u8),
+    /// Audio stream, with its channel number
+    Audio(u8),
+    /// Alpha channel of a video stream, with its channel number
+    Alpha(u8),
+    /// Subtitle stream, with its channel number (languages are told apart inside the stream)
+    Subtitle(u8)
+}
+
+impl StreamId {
+    /// Name of the file the stream is demuxed to, for a source file named `stem`.
+    ///
+    /// The first video channel keeps the name of the source, so that single-video files demux as they always did
+    #[must_use]
+    pub fn file_name(&self, stem: &str, extension: &str) -> String {
+        match *self {
+            Self::Video(0) => format!("{stem}.{extension}"),
+            Self::Video(chno) | Self::Audio(chno) => format!("{stem}_{chno}.{extension}"),
+            Self::Alpha(0) => format!("{stem}_alpha.{extension}"),
+            Self::Alpha(chno) => format!("{stem}_alpha_{chno}.{extension}"),
+            Self::Subtitle(chno) => format!("{stem}_sub{chno}.{extension}")
+        }
+    }
+}
+
+/// Destination of an elementary stream
+pub trait StreamSink {
+    /// Receive the next chunk of the stream
+    fn write_chunk(&mut self, chunk: &USMChunk) -> GICSResult<()>;
+
+    /// Called once every chunk of the stream has been received
+    fn finish(&mut self) -> GICSResult<()>;
+}
+
+/// Boxed stream sink, as handed out to demuxers
+pub type BoxedSink<'a> = Box<dyn StreamSink + 'a>;
+
+/// Sink writing the raw payloads of a stream to any writer (file, memory, pipe...)
+pub struct WriterSink<W: Write> {
+    writer: W
+}
+
+impl<W: Write> WriterSink<W> {
+    /// Write the stream to `writer`
+    pub const fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Get the writer back
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> StreamSink for WriterSink<W> {
+    fn write_chunk(&mut self, chunk: &USMChunk) -> GICSResult<()> {
+        self.writer.write_all(&chunk.payload)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> GICSResult<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Steps of the demuxing pipelines, and the warnings met on the way, for the caller to report as they come
+#[derive(Clone, Copy, Debug)]
+pub enum Progress<'a> {
+    /// Keys of the file named `.0` were found by trying every key of the key file
+    KeyFound(&'a str, &'a KeyCandidate),
+    /// Keys `(key2, key1)` of the file named `.0` were derived, and its audio is encrypted if `.3` is set
+    KeysDerived(&'a str, u32, u32, bool),
+    /// The headers of the file describe a video track
+    VideoTrack(&'a VideoInfo),
+    /// The audio track of channel `.0` is decoded, from the codec named `.1`
+    AudioTrack(u8, &'a str),
+    /// The keys of the file named `.0` could not be checked, so that the output may be garbage
+    UncheckedKeys(&'a str),
+    /// The file was demuxed into `.0` video and `.1` audio files
+    Demuxed(usize, usize),
+    /// Embedded subtitle tracks were extracted
+    EmbeddedSubtitles(usize),
+    /// SRT subtitle files were converted
+    ConvertedSubtitles(usize),
+    /// The headers of the file announce `.0` audio tracks, but `.1` were found
+    MissingAudio(usize, usize)
+}
+
+/// Blocks concealed in the audio streams of a file, by stream name
+pub type DamageReport = Vec<(String, DamagedBlocks)>;
+
+/// Sink decoding a HCA audio stream, and writing it as WAV to any writer, block by block as they come
+pub struct HCADecoderSink<'a, W: Write> {
+    buffer: Vec<u8>,
+    key2: [u8; 4],
+    key1: [u8; 4],
+    block_errors: BlockErrors,
+    pcm: PCMOptions,
+    name: String,
+    report: Option<&'a RefCell<DamageReport>>,
+    decoder: Option<HCAWavDecoder>,
+    output: W
+}
+
+impl<'a, W: Write> HCADecoderSink<'a, W> {
+    /// Decode the stream with the two halves of its key, into `output`
+    pub const fn new(output: W, key2: u32, key1: u32) -> Self {
+        Self {
+            buffer: Vec::new(),
+            key2: key2.to_le_bytes(),
+            key1: key1.to_le_bytes(),
+            block_errors: BlockErrors::Strict,
+            pcm: PCMOptions { format: PCMFormat::Int16, gain: 1.0 },
+            name: String::new(),
+            report: None,
+            decoder: None,
+            output
+        }
+    }
+
+    /// Handle damaged blocks as set by `block_errors`, naming the stream `name` in errors and in `report`, where the
+    /// blocks concealed are added
+    #[must_use]
+    pub fn with_block_errors(mut self, block_errors: BlockErrors, name: &str, report: &'a RefCell<DamageReport>) -> Self {
+        self.block_errors = block_errors;
+        self.name = name.into();
+        self.report = Some(report);
+        self
+    }
+
+    /// Write the WAV stream in the sample format and with the gain of `pcm`
+    #[must_use]
+    pub const fn with_pcm(mut self, pcm: PCMOptions) -> Self {
+        self.pcm = pcm;
+        self
+    }
+
+    /// Decode the header once it is buffered, then every whole block buffered
+    fn decode_buffer(&mut self) -> GICSResult<()> {
+        if self.decoder.is_none() {
+            match HCAFile::header_size(&self.buffer) {
+                Some(size) if self.buffer.len() >= size => {
+                    let mut reader = Cursor::new(&self.buffer);
+                    let mut hca = HCAFile::from_header(&mut reader, self.key2, self.key1)?;
+                    hca.set_block_errors(self.block_errors);
+                    hca.set_pcm_options(self.pcm);
+                    let read = usize::try_from(reader.position()).unwrap_or(size);
+                    self.decoder = Some(HCAWavDecoder::new(hca, &mut self.output)?);
+                    self.buffer.drain(..read);
+                },
+                _ => return Ok(())
+            }
+        }
+        if let Some(decoder) = self.decoder.as_mut() {
+            let block_size = decoder.block_size();
+            let whole = self.buffer.len() / block_size * block_size;
+            for block in self.buffer[..whole].chunks_exact_mut(block_size) {
+                decoder.write_block(block, &mut self.output)?;
+            }
+            self.buffer.drain(..whole);
+        }
+        Ok(())
+    }
+
+    /// Name the stream in `error`, if it has a name
+    fn named(&self, error: GICSError) -> GICSError {
+        if self.name.is_empty() { error } else { GICSError::new(&format!("{} : {error}", self.name)) }
+    }
+}
+
+impl<W: Write> StreamSink for HCADecoderSink<'_, W> {
+    fn write_chunk(&mut self, chunk: &USMChunk) -> GICSResult<()> {
+        self.buffer.extend_from_slice(&chunk.payload);
+        self.decode_buffer().map_err(|e| self.named(e))
+    }
+
+    fn finish(&mut self) -> GICSResult<()> {
+        let damaged = self.decoder.take()
+            .ok_or_else(|| GICSError::new("HCA stream ends before its header"))
+            .and_then(|decoder| decoder.finish(&mut self.output))
+            .map_err(|e| self.named(e))?;
+        if let Some(report) = self.report.filter(|_| !damaged.is_empty()) {
+            report.borrow_mut().push((self.name.clone(), damaged));
+        }
+        Ok(())
+    }
+}
+
+/// Sink decoding an ADX audio stream, and writing it as WAV to any writer, frame by frame as they come
+pub struct ADXDecoderSink<W: Write> {
+    buffer: Vec<u8>,
+    key2: [u8; 4],
+    key1: [u8; 4],
+    pcm: PCMOptions,
+    decoder: Option<ADXWavDecoder>,
+    output: W
+}
+
+impl<W: Write> ADXDecoderSink<W> {
+    /// Decode the stream with the two halves of its key, into `output`
+    pub const fn new(output: W, key2: u32, key1: u32) -> Self {
+        Self {
+            buffer: Vec::new(),
+            key2: key2.to_le_bytes(),
+            key1: key1.to_le_bytes(),
+            pcm: PCMOptions { format: PCMFormat::Int16, gain: 1.0 },
+            decoder: None,
+            output
+        }
+    }
+
+    /// Write the WAV stream in the sample format and with the gain of `pcm`
+    #[must_use]
+    pub const fn with_pcm(mut self, pcm: PCMOptions) -> Self {
+        self.pcm = pcm;
+        self
+    }
+}
+
+impl<W: Write> StreamSink for ADXDecoderSink<W> {
+    fn write_chunk(&mut self, chunk: &USMChunk) -> GICSResult<()> {
+        self.buffer.extend_from_slice(&chunk.payload);
+        if self.decoder.is_none() {
+            // Anything else than an ADX stream is reported as soon as its first bytes are there
+            match ADXFile::header_size(&self.buffer) {
+                None if self.buffer.len() < 4 => return Ok(()),
+                Some(size) if self.buffer.len() < size => return Ok(()),
+                _ => {
+                    let mut reader = Cursor::new(&self.buffer);
+                    let mut adx = ADXFile::from_header(&mut reader, self.key2, self.key1)?;
+                    adx.set_pcm_options(self.pcm);
+                    let read = usize::try_from(reader.position()).unwrap_or(self.buffer.len());
+                    self.decoder = Some(ADXWavDecoder::new(adx, &mut self.output)?);
+                    self.buffer.drain(..read);
+                }
+            }
+        }
+        if let Some(decoder) = self.decoder.as_mut() {
+            let frames_size = decoder.frames_size();
+            let whole = self.buffer.len() / frames_size * frames_size;
+            for frames in self.buffer[..whole].chunks_exact(frames_size) {
+                decoder.write_frames(frames, &mut self.output)?;
+            }
+            self.buffer.drain(..whole);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> GICSResult<()> {
+        self.decoder.take()
+            .ok_or_else(|| GICSError::new("ADX stream ends before its header"))?
+            .finish(&mut self.output)
+    }
+}
+
+/// Files which can be split into their elementary video and audio streams
 pub trait Demuxable {
-    fn demux(self, video_extract: bool, audio_extract: bool, output: &Path) -> GICSResult<(PathBuf, Vec<PathBuf>)>;
+    /// Demux the file into `output`, a directory.
+    ///
+    /// Returns the paths of every video (and alpha) stream, and the paths of every audio stream
+    fn demux(self, video_extract: bool, audio_extract: bool, output: &Path) -> GICSResult<(Vec<PathBuf>, Vec<PathBuf>)>;
+
+    /// Demux the file into caller-provided sinks.
+    ///
+    /// `sinks` is called once for every new stream met; streams for which it returns `None` are skipped.
+    /// Every sink is finished once the whole file has been read
+    fn demux_to<'a, F>(self, sinks: F) -> GICSResult<()>
+        where F: FnMut(StreamId) -> GICSResult<Option<BoxedSink<'a>>>;
 }
 
 fn output_paths_from(file: &Path, merge: bool, output: &Path) -> GICSResult<(PathBuf, PathBuf)> {
@@ -64,74 +368,330 @@ fn output_paths_from(file: &Path, merge: bool, output: &Path) -> GICSResult<(Pat
     Ok((tentative_out, tentative_mkv))
 }
 
-#[allow(clippy::too_many_arguments)]
-pub fn process_file(file: PathBuf, key2: u32, key1: u32, output: &Path, merge: bool, cleanup: bool, _subs: bool, ffmpeg_path: &str) -> GICSResult<()> {
-    let (output_directory, mkv_output) = output_paths_from(file.as_path(), merge, output)?;
+/// Options of the demuxing pipelines
+#[derive(Clone, Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct DemuxOptions {
+    /// Merge every stream into a MKV container
+    pub merge: bool,
+    /// Remove the demuxed files once merged
+    pub cleanup: bool,
+    /// Path of the `ffmpeg` binary doing the merge; the merge is done natively without it
+    pub ffmpeg_path: Option<String>,
+    /// SRT file, or subtitle root folder, to convert and merge along with the streams.
+    ///
+    /// A root folder holds `<Cutscene>_<LANG>.srt` files, directly or in one folder per language
+    pub subtitles: Option<PathBuf>,
+    /// Style of the ASS subtitles written
+    pub subtitle_style: ASSStyle,
+    /// Language and title of the audio tracks, by channel
+    pub audio_languages: AudioLanguages,
+    /// Demux without checking the keys against the start of the streams first
+    pub skip_key_check: bool,
+    /// Find the keys of every file by trying all the keys of the key file, rather than by looking its name up
+    pub discover_keys: bool,
+    /// What to do with damaged HCA blocks
+    pub block_errors: BlockErrors,
+    /// Sample format and gain of the audio streams decoded
+    pub pcm: PCMOptions,
+    /// Times the loop of looping HCA files is played when converted, once if 0
+    pub loop_count: u32
+}
+
+/// Language code ending the name of a `<stem>_<LANG>.srt` file, if it is a subtitle file of the cutscene `stem`
+fn srt_language(path: &Path, stem: &str) -> Option<String> {
+    if !path.extension().and_then(std::ffi::OsStr::to_str).is_some_and(|ext| ext.eq_ignore_ascii_case("srt")) {
+        return None;
+    }
+    let code = path.file_stem()?.to_str()?.strip_prefix(stem)?.strip_prefix('_')?;
+    // Another cutscene whose name starts like ours would leave more than a code
+    (!code.is_empty() && !code.contains('_')).then(|| code.to_string())
+}
+
+/// SRT files from `source` to merge with the USM file named `stem`, with the language code of each.
+///
+/// `source` is either a single SRT file, or a subtitle root folder holding `<stem>_<LANG>.srt` files,
+/// directly or in one folder per language
+fn srt_files_for(source: &Path, stem: &str) -> GICSResult<Vec<(PathBuf, String)>> {
+    if !source.is_dir() {
+        let code = source.file_stem()
+            .and_then(std::ffi::OsStr::to_str)
+            .and_then(|name| name.rsplit_once('_'))
+            .map_or_else(String::new, |(_, code)| code.to_string());
+        return Ok(vec![(source.to_path_buf(), code)]);
+    }
+    let mut files: Vec<(PathBuf, String)> = Vec::new();
+    for entry in std::fs::read_dir(source)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            for inner in std::fs::read_dir(&path)? {
+                let inner = inner?.path();
+                if let Some(code) = srt_language(&inner, stem).filter(|_| inner.is_file()) {
+                    files.push((inner, code));
+                }
+            }
+        } else if let Some(code) = srt_language(&path, stem) {
+            files.push((path, code));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Find the keys of a USM file by trying every key of the key file, then the key derived from its name alone.
+///
+/// The first key that decrypts the start of its streams correctly is returned, along with the outcome of its check :
+/// if no key could be checked, the first candidate is returned as inconclusive. The candidate tells where the key
+/// comes from, for the key file to be updated
+pub fn discover_keys(file: &Path, version_keys: &[Data]) -> GICSResult<(KeyCandidate, KeyCheck)> {
+    let filename: &str = file
+        .file_name().ok_or_else(|| GICSError::new("USM path has no file name"))?
+        .to_str().ok_or_else(|| GICSError::new("Unable to decode USM file name to UTF-8"))?;
+    let sample = USMFile::new(file.to_path_buf(), [0; 4], [0; 4])?.key_sample()?;
+    let candidates = candidate_keys(filename, version_keys);
+    sample.find_key(candidates.iter().map(|candidate| (candidate.key2, candidate.key1)))?
+        .map(|(index, check)| (candidates[index].clone(), check))
+        .ok_or_else(|| GICSError::new(&format!("Key mismatch : none of the {} known keys decrypts \"{filename}\"", candidates.len())))
+}
 
-    let file: USMFile = USMFile::new(file, key2.to_le_bytes(), key1.to_le_bytes());
-    let (video_path, audio_path_vec) = file.demux(true, true, output_directory.as_path())?;
-    println!("File demuxed. Collected one video and {} audio files.", audio_path_vec.len());
+/// Recover the key of a USM file missing from the key file, from known plaintext at the start of its streams.
+///
+/// The key comes with its `blk` key, to be added to the key file
+pub fn recover_key(file: &Path) -> GICSResult<KeyCandidate> {
+    let filename: &str = file
+        .file_name().ok_or_else(|| GICSError::new("USM path has no file name"))?
+        .to_str().ok_or_else(|| GICSError::new("Unable to decode USM file name to UTF-8"))?;
+    match USMFile::new(file.to_path_buf(), [0; 4], [0; 4])?.recover_key()? {
+        KeyRecovery::Found(key2, key1) => Ok(KeyCandidate {
+            version: None,
+            blk_key: blk_key(filename, key2, key1),
+            key2,
+            key1,
+            encrypted_audio: true,
+            listed: false
+        }),
+        KeyRecovery::Partial(mask) => Err(GICSError::new(&format!(
+            "Unable to recover the key of \"{filename}\" : known plaintext ran out with {} of the 32 bytes of its video mask solved",
+            mask.iter().flatten().count()
+        )))
+    }
+}
 
-    // Convert the HCAs right now
-    let a_paths = audio_path_vec.into_iter().enumerate().map(|(id, audio_path)| {
-            let basename: String = audio_path
-                .file_name().ok_or_else(|| GICSError::new("No file name in the provided path"))?
-                .to_str().ok_or_else(|| GICSError::new("Unable to decode path name into UTF-8"))?
-                .into();
-            let mut audio_output = output_directory.clone();
-            audio_output.push(&basename);
-            audio_output.set_extension("wav");
-            println!("Processing track #{}..", id);
-            process_hca(audio_path.clone(), key2, key1, audio_output.as_path(), cleanup)
-        }).collect::<GICSResult<Vec<PathBuf>>>()?;
+/// Check the keys of a USM file before demuxing it, failing on a mismatch
+fn check_keys<F: FnMut(Progress)>(file: &USMFile, stem: &str, key2: u32, key1: u32, progress: &mut F) -> GICSResult<()> {
+    match file.verify_key()? {
+        KeyCheck::Mismatch => Err(GICSError::new(&format!("Key mismatch : ({key2:08X}, {key1:08X}) do not decrypt \"{stem}\""))),
+        KeyCheck::Inconclusive => {
+            progress(Progress::UncheckedKeys(stem));
+            Ok(())
+        },
+        KeyCheck::Valid => Ok(())
+    }
+}
 
-    if merge {
-        MKVFile::attempt_merge(
-            mkv_output,
-            video_path.as_path(),
-            &a_paths.iter().map(PathBuf::as_path).collect::<Vec<&Path>>(),
-            ffmpeg_path
-        )?;
-        if cleanup {
-            // Remove video
-            std::fs::remove_file(video_path)?;
-            a_paths.iter()
+/// Demux a single USM file, convert its audio to WAV and optionally merge everything into a MKV.
+///
+/// `output` is either a directory, or the path of the MKV file if `options.merge` is set.
+/// The audio is only decrypted with the keys if `encrypted_audio` is set. Every step is handed to `progress` as it
+/// comes, and the blocks concealed in the audio are returned
+#[allow(clippy::too_many_lines)]
+pub fn process_file<F: FnMut(Progress)>(file: PathBuf, key2: u32, key1: u32, encrypted_audio: bool, output: &Path,
+    options: &DemuxOptions, progress: &mut F) -> GICSResult<DamageReport> {
+    let (output_directory, mkv_output) = output_paths_from(file.as_path(), options.merge, output)?;
+
+    let stem: String = file
+        .file_stem().ok_or_else(|| GICSError::new("USM path has no file stem"))?
+        .to_str().ok_or_else(|| GICSError::new("Unable to decode USM file stem to UTF-8"))?
+        .into();
+    let mut v_paths: Vec<PathBuf> = Vec::new();
+    let mut a_paths: Vec<(PathBuf, u8)> = Vec::new();
+    let subtitle_tracks: RefCell<Vec<SubtitleTrack>> = RefCell::new(Vec::new());
+    let damage_report: RefCell<DamageReport> = RefCell::new(Vec::new());
+
+    // The audio is decoded on the fly, straight into WAV files
+    let file: USMFile = USMFile::new(file, key2.to_le_bytes(), key1.to_le_bytes())?;
+    let metadata = file.metadata()?;
+    for video in &metadata.video {
+        progress(Progress::VideoTrack(video));
+    }
+    // Garbage is not worth writing
+    if !options.skip_key_check {
+        check_keys(&file, &stem, key2, key1, progress)?;
+    }
+    file.demux_to(|stream| -> GICSResult<Option<BoxedSink>> {
+        match stream {
+            // Every video channel, and its alpha channel, goes to its own file
+            StreamId::Video(_) | StreamId::Alpha(_) => {
+                let mut video_output = output_directory.clone();
+                video_output.push(stream.file_name(&stem, "ivf"));
+                let sink = WriterSink::new(BufWriter::new(File::create(&video_output)?));
+                v_paths.push(video_output);
+                Ok(Some(Box::new(sink)))
+            },
+            StreamId::Audio(chno) => {
+                let mut audio_output = output_directory.clone();
+                audio_output.push(stream.file_name(&stem, "wav"));
+                let info = metadata.audio_info(chno);
+                progress(Progress::AudioTrack(chno, info.map_or("HCA", AudioInfo::codec_name)));
+                let writer = BufWriter::new(File::create(&audio_output)?);
+                a_paths.push((audio_output, chno));
+                // Pick the decoder from the codec announced by the stream header
+                let (audio_key2, audio_key1) = if encrypted_audio { (key2, key1) } else { (0, 0) };
+                if info.is_some_and(AudioInfo::is_adx) {
+                    Ok(Some(Box::new(ADXDecoderSink::new(writer, audio_key2, audio_key1).with_pcm(options.pcm))))
+                } else {
+                    Ok(Some(Box::new(HCADecoderSink::new(writer, audio_key2, audio_key1)
+                        .with_block_errors(options.block_errors, &format!("audio track #{chno} of \"{stem}\""), &damage_report).with_pcm(options.pcm))))
+                }
+            },
+            StreamId::Subtitle(_) => Ok(Some(Box::new(SubtitleSink::new(&subtitle_tracks))))
+        }
+    })?;
+    progress(Progress::Demuxed(v_paths.len(), a_paths.len()));
+
+    // Embedded subtitles are written as ASS, ready to be merged, along with the language and title of their track
+    let mut s_files = subtitle_tracks.into_inner().iter().map(|track| {
+        let mut sub_output = output_directory.clone();
+        sub_output.push(format!("{stem}_sub{}.ass", track.language));
+        track.write_to(&sub_output, &options.subtitle_style)?;
+        Ok((sub_output, "und", format!("Subtitles #{}", track.language)))
+    }).collect::<GICSResult<Vec<(PathBuf, &str, String)>>>()?;
+    if !s_files.is_empty() {
+        progress(Progress::EmbeddedSubtitles(s_files.len()));
+    }
+    // And so are the SRT files we were given or found
+    if let Some(source) = &options.subtitles {
+        let srt_files = srt_files_for(source, &stem)?;
+        for (srt, code) in &srt_files {
+            let mut sub_output = output_directory.clone();
+            sub_output.push(srt.file_name().ok_or_else(|| GICSError::new("Subtitle path has no file name"))?);
+            sub_output.set_extension("ass");
+            SubtitleTrack::convert_srt(srt, &sub_output, &options.subtitle_style)
+                .map_err(|e| GICSError::new(&format!("Unable to convert \"{}\" : {e}", srt.display())))?;
+            let (language, title) = SubtitleTrack::genshin_language(code)
+                .map_or_else(|| ("und", code.clone()), |(iso, name)| (iso, name.to_string()));
+            s_files.push((sub_output, language, title));
+        }
+        progress(Progress::ConvertedSubtitles(srt_files.len()));
+    }
+    if !metadata.audio.is_empty() && metadata.audio.len() != a_paths.len() {
+        progress(Progress::MissingAudio(metadata.audio.len(), a_paths.len()));
+    }
+
+    if options.merge {
+        let v_paths_ref = v_paths.iter().map(PathBuf::as_path).collect::<Vec<&Path>>();
+        // Audio tracks are labelled by their channel, and named after their file if it has no title
+        let a_labels = a_paths.iter().map(|(path, chno)| {
+            let (language, title) = options.audio_languages.get(*chno).unwrap_or(("und", ""));
+            let title = if title.is_empty() {
+                path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+            } else {
+                title.into()
+            };
+            (path.as_path(), language, title)
+        }).collect::<Vec<(&Path, &str, String)>>();
+        let a_tracks = a_labels.iter()
+            .map(|(path, language, title)| MergeTrack { path, language, title })
+            .collect::<Vec<MergeTrack>>();
+        let s_tracks = s_files.iter()
+            .map(|(path, language, title)| MergeTrack { path, language, title })
+            .collect::<Vec<MergeTrack>>();
+        match options.ffmpeg_path.as_deref() {
+            Some(ffmpeg_path) => MKVFile::attempt_merge(mkv_output, &v_paths_ref, &a_tracks, &s_tracks, ffmpeg_path)?,
+            None => MKVFile::mux(&mkv_output, &v_paths_ref, &a_tracks, &s_tracks)?
+        }
+        if options.cleanup {
+            v_paths.iter().chain(a_paths.iter().map(|(path, _)| path)).chain(s_files.iter().map(|(path, _, _)| path))
                 .map(|p| std::fs::remove_file(p).map_err(GICSError::from))
                 .collect::<GICSResult<Vec<()>>>()?;
         }
-        Ok(())
-    } else {
-        Ok(())
     }
+    Ok(damage_report.into_inner())
 }
 
-pub fn process_hca(file: PathBuf, key2: u32, key1: u32, output: &Path, cleanup: bool) -> GICSResult<PathBuf> {
-    let audio_file: HCAFile = HCAFile::new(file.clone(), key2.to_le_bytes(), key1.to_le_bytes())?;
-    let outfile = audio_file.convert_to_wav(output)?;
-    if cleanup {
+/// Convert a HCA file to a WAV file at `output`, returning the path of the WAV file and the blocks concealed.
+///
+/// Of `options`, only the cleanup, key check, damaged block handling, PCM and loop options are used
+pub fn process_hca(file: PathBuf, key2: u32, key1: u32, output: &Path, options: &DemuxOptions) -> GICSResult<(PathBuf, DamagedBlocks)> {
+    let mut audio_file: HCAFile = HCAFile::new(&file, key2.to_le_bytes(), key1.to_le_bytes())?;
+    if !options.skip_key_check && audio_file.verify_key(KEY_CHECK_BLOCKS)? == KeyCheck::Mismatch {
+        return Err(GICSError::new(&format!("Key mismatch : ({key2:08X}, {key1:08X}) do not decrypt \"{}\"", file.display())));
+    }
+    audio_file.set_block_errors(options.block_errors);
+    audio_file.set_pcm_options(options.pcm);
+    audio_file.set_loop_count(options.loop_count);
+    let (outfile, damaged) = audio_file.convert_to_wav(output)?;
+    if options.cleanup {
         std::fs::remove_file(file)?;
     }
-    Ok(outfile)
+    Ok((outfile, damaged))
 }
 
-pub fn process_directory(folder: PathBuf, version_keys: &[Data], output: &Path, merge: bool, cleanup: bool, subs: bool, ffmpeg_path: &str) -> GICSResult<()> {
-    if output.exists() && !output.is_dir() {
-        println!("{:?}", output);
-        return Err(GICSError::new("Provided output path is not a directory; this would overwrite every result. Aborting"));
+/// Decrypt a HCA file into a plain HCA file at `output`, without decoding it.
+///
+/// The path of the new file is returned, with the damaged blocks copied as they are.
+/// Of `options`, only the key check and damaged block handling are used
+pub fn decrypt_hca(file: &Path, key2: u32, key1: u32, output: &Path, options: &DemuxOptions) -> GICSResult<(PathBuf, DamagedBlocks)> {
+    let mut audio_file: HCAFile = HCAFile::new(file, key2.to_le_bytes(), key1.to_le_bytes())?;
+    if !options.skip_key_check && audio_file.verify_key(KEY_CHECK_BLOCKS)? == KeyCheck::Mismatch {
+        return Err(GICSError::new(&format!("Key mismatch : ({key2:08X}, {key1:08X}) do not decrypt \"{}\"", file.display())));
+    }
+    rewrite_hca(audio_file, 0, (0, 0), output, options)
+}
+
+/// Encrypt a plain HCA file with cipher type 56 and the two halves of a key into a HCA file at `output`, without
+/// decoding it.
+///
+/// The path of the new file is returned, with the damaged blocks copied as they are.
+/// Of `options`, only the damaged block handling is used
+pub fn encrypt_hca(file: &Path, key2: u32, key1: u32, output: &Path, options: &DemuxOptions) -> GICSResult<(PathBuf, DamagedBlocks)> {
+    let audio_file: HCAFile = HCAFile::new(file, [0; 4], [0; 4])?;
+    if audio_file.cipher_type() == 56 {
+        return Err(GICSError::new(&format!("\"{}\" is already encrypted with a key : decrypt it first", file.display())));
     }
+    rewrite_hca(audio_file, 56, (key2, key1), output, options)
+}
+
+/// Write `audio_file` with its blocks enciphered with `cipher_type` and the two halves of a key
+fn rewrite_hca(mut audio_file: HCAFile, cipher_type: u16, (key2, key1): (u32, u32), output: &Path, options: &DemuxOptions) -> GICSResult<(PathBuf, DamagedBlocks)> {
+    audio_file.set_block_errors(options.block_errors);
+    let damaged = audio_file.write_encrypted(cipher_type, key2.to_le_bytes(), key1.to_le_bytes(), &mut BufWriter::new(File::create(output)?))?;
+    Ok((PathBuf::from(output), damaged))
+}
+
+/// List the USM files found in `folder`, sorted by name
+pub fn usm_files_in(folder: &Path) -> GICSResult<Vec<PathBuf>> {
+    files_in(folder, "usm")
+}
+
+/// List the HCA files found in `folder`, sorted by name
+pub fn hca_files_in(folder: &Path) -> GICSResult<Vec<PathBuf>> {
+    files_in(folder, "hca")
+}
+
+fn files_in(folder: &Path, extension: &str) -> GICSResult<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = Vec::new();
     for entry in std::fs::read_dir(folder)? {
         let path = entry?.path();
-        if !path.is_file() {
-            println!("Skipping file \"{}\"", path.to_str().ok_or_else(|| GICSError::new("Unable to decode file path to UTF-8"))?);
-            continue;
-        }
-        // File has to have an extension and be a USM
-        if path.extension().is_none()
-            || path.extension().unwrap().to_str().is_none()
-            || path.extension().unwrap().to_str().unwrap() != "usm" {
-                println!("Skipping file \"{}\"", path.to_str().ok_or_else(|| GICSError::new("Unable to decode file path to UTF-8"))?);
-                continue;
+        if path.is_file() && path.extension().and_then(std::ffi::OsStr::to_str) == Some(extension) {
+            files.push(path);
         }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Demux every USM file found in `folder`, deriving their keys from `version_keys`, and return the blocks concealed in
+/// their audio.
+///
+/// Every step is handed to `progress` as it comes
+pub fn process_directory<F: FnMut(Progress)>(folder: &Path, version_keys: &[Data], output: &Path, options: &DemuxOptions,
+    progress: &mut F) -> GICSResult<DamageReport> {
+    if output.exists() && !output.is_dir() {
+        return Err(GICSError::new("Provided output path is not a directory; this would overwrite every result. Aborting"));
+    }
+    let mut damage_report: DamageReport = Vec::new();
+    for path in usm_files_in(folder)? {
         // Copy the entry name
         let mut outpath = PathBuf::from(output);
         let basename = path
@@ -140,9 +700,16 @@ pub fn process_directory(folder: PathBuf, version_keys: &[Data], output: &Path,
         outpath.push(basename);
         outpath.set_extension("mkv");
         // Find keys
-        let (key_two, key_one) = definite_version_keys(basename, Some(version_keys), None, None)?;
-        println!("Keys derived for \"{}\" : ({:08X}, {:08X})", basename, key_two, key_one);
-        process_file(path, key_two, key_one, outpath.as_path(), merge, cleanup, subs, ffmpeg_path)?;
+        let (key_two, key_one, encrypted_audio) = if options.discover_keys {
+            let (candidate, _) = discover_keys(&path, version_keys)?;
+            progress(Progress::KeyFound(basename, &candidate));
+            (candidate.key2, candidate.key1, candidate.encrypted_audio)
+        } else {
+            let (key_two, key_one) = definite_version_keys(basename, Some(version_keys), None, None)?;
+            (key_two, key_one, audio_encrypted(basename, Some(version_keys)))
+        };
+        progress(Progress::KeysDerived(basename, key_two, key_one, encrypted_audio));
+        damage_report.extend(process_file(path, key_two, key_one, encrypted_audio, outpath.as_path(), options, progress)?);
     }
-    Ok(())
+    Ok(damage_report)
 }
\ No newline at end of file
@@ -1,16 +1,22 @@
+//! Error type shared by the whole crate
+
 use std::error::Error;
 use std::fmt::{
     Display,
     Formatter
 };
 
+/// Error returned by every fallible operation of the crate
 #[derive(Debug)]
 pub struct GICSError {
     message: String
 }
+/// Result type carrying a [`GICSError`]
 pub type GICSResult<T> = Result<T, GICSError>;
 
 impl GICSError {
+    /// Build an error from a message
+    #[must_use]
     pub fn new(message: &str) -> Self {
         Self { message: message.into() }
     }
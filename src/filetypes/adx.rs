@@ -0,0 +1,306 @@
+#[derive(Default)]
+struct ADXHeader {
+    encoding_type: u8,
+    block_size: u8,
+    channel_count: u8,
+    sampling_rate: u32,
+    total_samples: u32,
+    highpass_frequency: u16,
+    encryption: u8
+}
+
+/// Key of an encrypted ADX stream.
+///
+/// The scale of every frame is masked with the output of a linear congruential generator,
+/// which this key seeds (`start`) and drives (`mult` and `add`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ADXKey {
+    /// Initial value of the generator
+    pub start: u16,
+    /// Multiplier of the generator
+    pub mult: u16,
+    /// Increment of the generator
+    pub add: u16
+}
+
+impl ADXKey {
+    /// Key of a type 9 stream, derived from its 64 bits key code
+    #[must_use]
+    pub const fn from_keycode(keycode: u64) -> Self {
+        if keycode == 0 {
+            return Self { start: 0, mult: 0, add: 0 };
+        }
+        let keycode = keycode - 1;
+        Self {
+            start: (keycode >> 27 & 0x7FFF) as u16,
+            mult: (keycode >> 12 & 0x7FFC | 1) as u16,
+            add: (keycode << 1 & 0x7FFE | 1) as u16
+        }
+    }
+
+    /// Key of a type 9 stream, from the two halves of its key code
+    #[must_use]
+    pub const fn from_halves(key2: [u8; 4], key1: [u8; 4]) -> Self {
+        Self::from_keycode((u32::from_le_bytes(key2) as u64) << 32 | u32::from_le_bytes(key1) as u64)
+    }
+
+    const fn next(self, xor: u16) -> u16 {
+        (xor as u32 * self.mult as u32 + self.add as u32) as u16 & 0x7FFF
+    }
+}
+
+/// An ADX audio file, read and ready to be decoded
+pub struct ADXFile {
+    header: ADXHeader,
+    key: ADXKey,
+    pcm: PCMOptions,
+    data: Vec<u8>
+}
+
+impl ADXFile {
+    /// Read an ADX file and prepare its decoding with the two halves of its key (used by type 9 encryption)
+    pub fn new(path: &Path, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<Self> {
+        if !path.exists() {
+            return Err(GICSError::new("Could not find file"));
+        }
+        if path.extension().and_then(std::ffi::OsStr::to_str) != Some("adx") {
+            return Err(GICSError::new("File extension isn't ADX"));
+        }
+        Self::from_reader(&mut BufReader::new(File::open(path)?), key2, key1)
+    }
+
+    /// Read an ADX stream from any source and prepare its decoding with the two halves of its key
+    pub fn from_reader<R: Read>(reader: &mut R, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<Self> {
+        let mut res = Self::from_header(reader, key2, key1)?;
+        reader.read_to_end(&mut res.data)?;
+        Ok(res)
+    }
+
+    /// Read only the header of an ADX stream, for its frames to be decoded as they come with [`ADXWavDecoder`]
+    pub fn from_header<R: Read>(reader: &mut R, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<Self> {
+        let mut file: Vec<u8> = vec![0; 4];
+        reader.read_exact(&mut file)?;
+        let header_size = Self::header_size(&file).filter(|&size| size >= 0x14)
+            .ok_or_else(|| GICSError::new("Not an ADX stream"))?;
+        file.resize(header_size, 0);
+        reader.read_exact(&mut file[4..])?;
+        // The data starts right after the copyright notice
+        let copyright_offset = header_size - 4;
+        if file.get(copyright_offset - 2..copyright_offset + 4) != Some(b"(c)CRI") {
+            return Err(GICSError::new("ADX copyright notice not found"));
+        }
+
+        let header = ADXHeader {
+            encoding_type: file[4],
+            block_size: file[5],
+            channel_count: file[7],
+            sampling_rate: make_be32(&file[8..12]),
+            total_samples: make_be32(&file[12..16]),
+            highpass_frequency: make_be16(&file[16..18]),
+            encryption: file[0x13]
+        };
+        if !matches!(header.encoding_type, 3 | 4) {
+            return Err(GICSError::new(&format!("ADX encoding type not supported: {}", header.encoding_type)));
+        }
+        if file[6] != 4 || header.block_size < 3 || header.channel_count == 0 {
+            return Err(GICSError::new("Invalid ADX frame layout"));
+        }
+        if !matches!(header.encryption, 0 | 8 | 9) {
+            return Err(GICSError::new(&format!("ADX encryption type not supported: {}", header.encryption)));
+        }
+
+        Ok(Self {
+            header,
+            key: ADXKey::from_halves(key2, key1),
+            pcm: PCMOptions::default(),
+            data: Vec::new()
+        })
+    }
+
+    /// Size of the header of an ADX stream, up to its copyright notice, from its first 4 bytes if `start` holds them
+    #[must_use]
+    pub fn header_size(start: &[u8]) -> Option<usize> {
+        start.get(0..4).filter(|start| start[0..2] == [0x80, 0x00]).map(|start| usize::from(make_be16(&start[2..4])) + 4)
+    }
+
+    /// Use a key given as is, rather than derived from a key code.
+    ///
+    /// Type 8 keys are derived from key strings, so they can only be given this way
+    pub const fn set_key(&mut self, key: ADXKey) {
+        self.key = key;
+    }
+
+    /// Choose the sample format and gain of the WAV files written
+    pub const fn set_pcm_options(&mut self, pcm: PCMOptions) {
+        self.pcm = pcm;
+    }
+
+    /// Encryption type of the stream (0 when clear, 8 or 9 otherwise)
+    #[must_use]
+    pub const fn encryption(&self) -> u8 {
+        self.header.encryption
+    }
+
+    /// Decode the whole file into a PCM WAV file at `path`
+    pub fn convert_to_wav(self, path: &Path) -> GICSResult<PathBuf> {
+        let wav_path = PathBuf::from(path);
+        let mut wav_file = BufWriter::new(File::create(&wav_path)?);
+        self.write_wav(&mut wav_file)?;
+        Ok(wav_path)
+    }
+
+    /// Prediction coefficients, in 4.12 fixed point, derived from the high-pass cutoff frequency
+    fn coefficients(&self) -> (i32, i32) {
+        let z = (2.0 * std::f64::consts::PI * f64::from(self.header.highpass_frequency) / f64::from(self.header.sampling_rate.max(1))).cos();
+        let a = std::f64::consts::SQRT_2 - z;
+        let b = std::f64::consts::SQRT_2 - 1.0;
+        let c = (a - ((a + b) * (a - b)).sqrt()) / b;
+        ((c * 8192.0) as i32, (c * c * -4096.0) as i32)
+    }
+
+    /// Decode the whole file as a PCM WAV stream into `wav_file`, in the format set with [`ADXFile::set_pcm_options`]
+    pub fn write_wav<W: Write>(mut self, wav_file: &mut W) -> GICSResult<()> {
+        let data = std::mem::take(&mut self.data);
+        let mut decoder = ADXWavDecoder::new(self, wav_file)?;
+        for frames in data.chunks_exact(decoder.frames_size()) {
+            decoder.write_frames(frames, wav_file)?;
+        }
+        decoder.finish(wav_file)
+    }
+}
+
+/// Decoder of an ADX stream into a PCM WAV stream, fed with its frames one at a time
+pub struct ADXWavDecoder {
+    file: ADXFile,
+    coefficients: (i32, i32),
+    history: Vec<(i32, i32)>,
+    xor: u16,
+    samples: Vec<i16>,
+    pcm: Vec<u8>,
+    remaining: usize
+}
+
+impl ADXWavDecoder {
+    /// Write the WAV header of `file` into `wav_file`, in the format set with [`ADXFile::set_pcm_options`]
+    pub fn new<W: Write>(file: ADXFile, wav_file: &mut W) -> GICSResult<Self> {
+        if file.header.encryption == 8 && file.key == ADXKey::default() {
+            return Err(GICSError::new("ADX type 8 encryption needs its key to be set"));
+        }
+        let channel_count = usize::from(file.header.channel_count);
+        let frame_samples = (usize::from(file.header.block_size) - 2) * 2;
+
+        let mut wav_riff = WaveRiff::for_format(file.pcm.format, u16::from(file.header.channel_count), file.header.sampling_rate);
+        // Sizes are written on 32 bits, which the sample count of the header can overflow
+        let too_long = || GICSError::new("Decoded audio too long for a WAV file");
+        let mut wav_data = WaveData::default();
+        wav_data.set_data_size(file.header.total_samples.checked_mul(u32::from(wav_riff.fmt_sampling_size)).ok_or_else(too_long)?);
+        wav_riff.riff_size = (wav_riff.header_size() + 8).checked_add(wav_data.data_size).ok_or_else(too_long)?;
+        wav_file.write_all(&wav_riff.build_byte_array())?;
+        wav_file.write_all(&wav_data.build_byte_array())?;
+
+        let samples: Vec<i16> = vec![0; frame_samples * channel_count];
+        Ok(Self {
+            coefficients: file.coefficients(),
+            history: vec![(0, 0); channel_count],
+            xor: file.key.start,
+            pcm: Vec::with_capacity(samples.len() * usize::from(file.pcm.format.bits() / 8)),
+            samples,
+            remaining: file.header.total_samples as usize,
+            file
+        })
+    }
+
+    /// Size of the frames of every channel, interleaved
+    #[must_use]
+    pub fn frames_size(&self) -> usize {
+        usize::from(self.file.header.block_size) * usize::from(self.file.header.channel_count)
+    }
+
+    /// Decode the next frame of every channel, and write their samples into `wav_file`.
+    ///
+    /// Frames past the sample count of the header are ignored
+    pub fn write_frames<W: Write>(&mut self, frames: &[u8], wav_file: &mut W) -> GICSResult<()> {
+        if self.remaining == 0 {
+            return Ok(());
+        }
+        if frames.len() != self.frames_size() {
+            return Err(GICSError::new(&format!("ADX frames are {} bytes long instead of {}", frames.len(), self.frames_size())));
+        }
+        let header = &self.file.header;
+        let channel_count = usize::from(header.channel_count);
+        let frame_samples = self.samples.len() / channel_count;
+        let (coef1, coef2) = self.coefficients;
+        for (channel, frame) in frames.chunks_exact(usize::from(header.block_size)).enumerate() {
+            let scale = i32::from(make_be16(&frame[0..2]));
+            let scale = match (header.encryption, header.encoding_type) {
+                (0, 4) => 1 << (12 - scale.clamp(0, 12)),
+                (0, _) => scale + 1,
+                _ => {
+                    let scale = (scale ^ i32::from(self.xor)) & 0x1FFF;
+                    self.xor = self.file.key.next(self.xor);
+                    scale + 1
+                }
+            };
+            let (hist1, hist2) = &mut self.history[channel];
+            let nibbles = frame[2..].iter().flat_map(|&byte| [byte >> 4, byte & 0xF]);
+            for (index, nibble) in nibbles.enumerate() {
+                let nibble = if nibble >= 8 { i32::from(nibble) - 16 } else { i32::from(nibble) };
+                let sample = (nibble * scale + ((coef1 * *hist1) >> 12) + ((coef2 * *hist2) >> 12))
+                    .clamp(i32::from(i16::MIN), i32::from(i16::MAX));
+                *hist2 = *hist1;
+                *hist1 = sample;
+                self.samples[index * channel_count + channel] = sample as i16;
+            }
+        }
+        let count = frame_samples.min(self.remaining);
+        self.pcm.clear();
+        for sample in &self.samples[..count * channel_count] {
+            self.file.pcm.format.push_sample(f64::from(*sample) / f64::from(i16::MAX) * f64::from(self.file.pcm.gain), &mut self.pcm);
+        }
+        wav_file.write_all(&self.pcm)?;
+        self.remaining -= count;
+        Ok(())
+    }
+
+    /// Flush `wav_file` once the stream is written
+    pub fn finish<W: Write>(self, wav_file: &mut W) -> GICSResult<()> {
+        wav_file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod adx_tests {
+    use super::*;
+
+    /// Header of a stereo ADX stream of `total_samples` samples per channel
+    fn header(total_samples: u32) -> Vec<u8> {
+        let mut header: Vec<u8> = vec![0x80, 0x00, 0x00, 0x1C, 3, 18, 4, 2];
+        header.extend(48000_u32.to_be_bytes());
+        header.extend(total_samples.to_be_bytes());
+        header.extend(500_u16.to_be_bytes());
+        header.resize(0x1A, 0);
+        header.extend(b"(c)CRI");
+        header
+    }
+
+    #[test]
+    fn wav_sizes() {
+        let file = ADXFile::from_header(&mut header(0x1000).as_slice(), [0; 4], [0; 4]).unwrap();
+        let mut wav: Vec<u8> = Vec::new();
+        ADXWavDecoder::new(file, &mut wav).unwrap();
+        assert_eq!(wav.len(), 0x2C);
+        assert_eq!(wav[4..8], (0x24_u32 + 0x4000).to_le_bytes());
+        assert_eq!(wav[0x28..0x2C], 0x4000_u32.to_le_bytes());
+    }
+
+    #[test]
+    fn too_long_for_a_wav_file() {
+        let mut file = ADXFile::from_header(&mut header(0x4000_0000).as_slice(), [0; 4], [0; 4]).unwrap();
+        file.set_pcm_options(PCMOptions { format: PCMFormat::Float, gain: 1.0 });
+        assert!(ADXWavDecoder::new(file, &mut Vec::new()).is_err());
+        // The data fits, but not the headers along with it
+        let file = ADXFile::from_header(&mut header(0x3FFF_FFFF).as_slice(), [0; 4], [0; 4]).unwrap();
+        assert!(ADXWavDecoder::new(file, &mut Vec::new()).is_err());
+    }
+}
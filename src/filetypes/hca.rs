@@ -5,6 +5,8 @@ struct HCAHeader {
     sampling_rate: u32,
     block_count: u32,
     block_size: u16,
+    encoder_delay: u16,
+    encoder_padding: u16,
 
     comp_r01: u32,
     comp_r02: u32,
@@ -19,10 +21,18 @@ struct HCAHeader {
     ath_type: u16,
 
     loop_flag: bool,
+    loop_start: u32,
+    loop_end: u32,
+    loop_start_delay: u16,
+    loop_end_padding: u16,
 
     cipher_type: u16,
+    cipher_offset: usize,
 
-    volume: f32
+    volume: f32,
+    comment: Option<String>,
+    /// Offset of every chunk in the header
+    chunks: Vec<usize>
 }
 
 impl Default for HCAHeader {
@@ -34,6 +44,8 @@ impl Default for HCAHeader {
             sampling_rate: 0,
             block_count: 0,
             block_size: 0,
+            encoder_delay: 0,
+            encoder_padding: 0,
 
             comp_r01: 0,
             comp_r02: 0,
@@ -47,19 +59,92 @@ impl Default for HCAHeader {
 
             ath_type: 0,
             loop_flag: false,
+            loop_start: 0,
+            loop_end: 0,
+            loop_start_delay: 0,
+            loop_end_padding: 0,
             cipher_type: 0,
-            volume: 0.0
+            cipher_offset: 0,
+            volume: 0.0,
+            comment: None,
+            chunks: Vec::new()
         }
     }
 }
 
+/// What to do with HCA blocks that cannot be decoded
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlockErrors {
+    /// Stop decoding, with an error naming the block
+    #[default]
+    Strict,
+    /// Output silence in place of the block
+    Silence,
+    /// Output the last block decoded again in place of the block
+    Repeat
+}
+
+/// Why a HCA block cannot be decoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum BlockDamage {
+    /// The block fails its checksum
+    Checksum,
+    /// The block does not start with a sync word
+    Sync,
+    /// The scale factors of the block are out of range
+    Scales
+}
+
+impl Display for BlockDamage {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Checksum => write!(fmt, "bad checksum"),
+            Self::Sync => write!(fmt, "missing sync word"),
+            Self::Scales => write!(fmt, "scale factors out of range")
+        }
+    }
+}
+
+/// Blocks of a HCA stream concealed while decoding it
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DamagedBlocks {
+    /// Index of every damaged block, and what is wrong with it
+    pub blocks: Vec<(usize, BlockDamage)>,
+    /// Number of blocks in the stream
+    pub total: usize
+}
+
+impl DamagedBlocks {
+    /// Whether every block was decoded
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+impl Display for DamagedBlocks {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{} of {} blocks damaged", self.blocks.len(), self.total)?;
+        for (position, (index, damage)) in self.blocks.iter().take(8).enumerate() {
+            write!(fmt, "{} #{index} ({damage})", if position == 0 { " :" } else { "," })?;
+        }
+        if self.blocks.len() > 8 {
+            write!(fmt, " and {} more", self.blocks.len() - 8)?;
+        }
+        Ok(())
+    }
+}
+
+/// A HCA audio file, read and ready to be decoded
 pub struct HCAFile {
-    filename: PathBuf,
     key1: [u8; 4],
     key2: [u8; 4],
     cipher_table: [u8; 0x100],
     ath_table: [u8; 0x80],
     encrypted: bool,
+    block_errors: BlockErrors,
+    pcm: PCMOptions,
+    loop_count: u32,
     hca_header: HCAHeader,
     hca_channel: Vec<Channel>,
     header: Vec<u8>,
@@ -67,32 +152,140 @@ pub struct HCAFile {
 }
 
 impl HCAFile {
-    pub fn new(path: PathBuf, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<Self> {
-        let mut res = Self {
-            filename: path,
+    /// Read a HCA file and prepare its decoding with the two halves of its key
+    pub fn new(path: &Path, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<Self> {
+        if !path.exists() {
+            return Err(GICSError::new("Could not find file"));
+        }
+        if path.extension().and_then(std::ffi::OsStr::to_str) != Some("hca") {
+            return Err(GICSError::new("File extension isn't HCA"));
+        }
+        Self::from_reader(&mut BufReader::new(File::open(path)?), key2, key1)
+    }
+
+    /// A file with nothing read yet
+    fn blank(key2: [u8; 4], key1: [u8; 4]) -> Self {
+        Self {
             key1, key2,
             cipher_table: [0; 0x100],
             ath_table: [0; 0x80],
             encrypted: false,
+            block_errors: BlockErrors::default(),
+            pcm: PCMOptions::default(),
+            loop_count: 1,
             hca_header: HCAHeader::default(),
             hca_channel: Vec::new(),
             header: Vec::new(),
             data: Vec::new()
-        };
-        res.read_header()?;
+        }
+    }
+
+    /// Read a HCA stream from any source and prepare its decoding with the two halves of its key
+    pub fn from_reader<R: Read>(reader: &mut R, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<Self> {
+        let mut res = Self::blank(key2, key1);
+        res.read_header(reader)?;
+        let data_size: usize = usize::from(res.hca_header.block_size) * res.hca_header.block_count as usize;
+        res.data = vec![0; data_size];
+        reader.read_exact(&mut res.data)?;
         Ok(res)
     }
 
-    #[allow(clippy::too_many_lines)]
-    fn read_header(&mut self) -> GICSResult<()> {
-        if !self.filename.exists() {
-            return Err(GICSError::new("Could not find file"));
+    /// Read the start of a HCA stream : its header, and as many whole blocks as `reader` holds
+    pub fn from_partial_reader<R: Read>(reader: &mut R, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<Self> {
+        let mut res = Self::blank(key2, key1);
+        res.read_header(reader)?;
+        let block_size = usize::from(res.hca_header.block_size);
+        reader.take((block_size * res.hca_header.block_count as usize) as u64).read_to_end(&mut res.data)?;
+        let blocks = res.data.len() / block_size.max(1);
+        res.data.truncate(blocks * block_size);
+        res.hca_header.block_count = blocks as u32;
+        Ok(res)
+    }
+
+    /// Read only the header of a HCA stream, for its blocks to be decoded as they come with [`HCAWavDecoder`]
+    pub fn from_header<R: Read>(reader: &mut R, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<Self> {
+        let mut res = Self::blank(key2, key1);
+        res.read_header(reader)?;
+        Ok(res)
+    }
+
+    /// Size of the header of a HCA stream, from its first 8 bytes, if `start` holds them
+    #[must_use]
+    pub fn header_size(start: &[u8]) -> Option<usize> {
+        start.get(6..8).map(|size| usize::from(u16::from_be_bytes([size[0], size[1]])))
+    }
+
+    /// Choose what to do with blocks that cannot be decoded
+    pub const fn set_block_errors(&mut self, block_errors: BlockErrors) {
+        self.block_errors = block_errors;
+    }
+
+    /// Choose the sample format and gain of the WAV files written
+    pub const fn set_pcm_options(&mut self, pcm: PCMOptions) {
+        self.pcm = pcm;
+    }
+
+    /// Play the loop of looping files `loop_count` times (at least once) in the WAV files written
+    pub const fn set_loop_count(&mut self, loop_count: u32) {
+        self.loop_count = loop_count;
+    }
+
+    /// First sample of the loop, and the sample right after its end, if the file loops
+    #[must_use]
+    pub fn loop_points(&self) -> Option<(u32, u32)> {
+        let header = &self.hca_header;
+        let start = header.loop_start.checked_mul(0x400)?.checked_add(u32::from(header.loop_start_delay))?;
+        let end = header.loop_end.checked_add(1)?.checked_mul(0x400)?.saturating_sub(u32::from(header.loop_end_padding));
+        (header.loop_flag && start < end).then_some((start, end))
+    }
+
+    /// Cipher type of the stream : 0 for plain streams, 1 for the keyless cipher, and 56 for the keyed one
+    #[must_use]
+    pub const fn cipher_type(&self) -> u16 {
+        self.hca_header.cipher_type
+    }
+
+    /// Check the key against the first `max_blocks` blocks of the stream.
+    ///
+    /// Sync words and checksums do not depend on the key, but the decoder must read every block without overrunning
+    /// it, and leave nothing but zero padding behind : garbage bits decoded with a wrong key rarely do. Silent and
+    /// damaged blocks tell nothing, so the result is inconclusive if every block checked is one of them
+    pub fn verify_key(&mut self, max_blocks: usize) -> GICSResult<KeyCheck> {
+        if self.hca_header.cipher_type != 56 {
+            return Ok(KeyCheck::Valid);
         }
-        if self.filename.extension().unwrap().to_str().unwrap() != "hca" {
-            return Err(GICSError::new("File extension isn't HCA"));
+        let block_size = usize::from(self.hca_header.block_size);
+        if block_size < 8 {
+            return Ok(KeyCheck::Inconclusive);
         }
-        let mut fs: File = File::open(&self.filename)?;
+        let mut res = KeyCheck::Inconclusive;
+        for index in 0..max_blocks.min(self.hca_header.block_count as usize) {
+            let mut block: Vec<u8> = self.data[index * block_size..(index + 1) * block_size].to_vec();
+            // Damaged blocks are left to the decoder, to be handled as set with `set_block_errors`
+            if Self::checksum(&block, block_size) != 0 || block[..2] != [0xFF, 0xFF] {
+                continue;
+            }
+            if block[2..block_size - 2].iter().all(|&byte| byte == 0) {
+                continue;
+            }
+            let Ok(bits) = self.decode_block(&mut block) else {
+                res = KeyCheck::Mismatch;
+                break;
+            };
+            let bits = usize::try_from(bits).unwrap_or(usize::MAX);
+            if bits + 14 > block_size * 8 || block[bits.div_ceil(8).min(block_size - 2)..block_size - 2].iter().any(|&byte| byte != 0) {
+                res = KeyCheck::Mismatch;
+                break;
+            }
+            res = KeyCheck::Valid;
+        }
+        // Decoding left its state in the channels
+        self.channel_init()?;
+        Ok(res)
+    }
 
+    #[allow(clippy::too_many_lines)]
+    fn read_header<R: Read>(&mut self, fs: &mut R) -> GICSResult<()> {
         let mut hca_byte: [u8; 8] = [0; 8];
         fs.read_exact(&mut hca_byte)?;
 
@@ -114,11 +307,14 @@ impl HCAFile {
             return Err(GICSError::new("unknown signature for version and data_offset block"));
         }
 
+        if self.hca_header.data_offset < 8 {
+            return Err(GICSError::new("HCA data offset is inside the first header block"));
+        }
         let mut header: Vec<u8> = vec![0; usize::from(self.hca_header.data_offset)];
-        fs.seek(SeekFrom::Start(0))?;
-        fs.read_exact(&mut header)?;
+        fs.read_exact(&mut header[8..])?;
         header.iter_mut().zip(&hca_byte).for_each(|(dest, source)| *dest = *source);
         let mut header_offset: usize = 8;
+        self.hca_header.chunks.push(0);
 
         // Format
         sign = u32::from_le_bytes([
@@ -129,11 +325,11 @@ impl HCAFile {
         ]) & magic;
 
         if sign == 0x0074_6D66 {
-            header[header_offset .. header_offset + 4].iter_mut().zip(&hca_byte).for_each(|(dest, source)| *dest = *source);
-            //header[header_offset] = converted[0];
+            header[header_offset..].iter_mut().zip(&sign.to_le_bytes()).for_each(|(dest, source)| *dest = *source);
+            self.hca_header.chunks.push(header_offset);
             self.hca_header.channel_count = u16::from(header[header_offset + 4]);
             let mut sampling_rate: [u8; 4] = [0; 4];
-            (&mut sampling_rate[1..4]).iter_mut().zip(&header[header_offset + 5 .. header_offset + 8]).for_each(|(dest, source)| *dest = *source);
+            sampling_rate[1..4].iter_mut().zip(&header[header_offset + 5 .. header_offset + 8]).for_each(|(dest, source)| *dest = *source);
             self.hca_header.sampling_rate = u32::from_be_bytes(sampling_rate);
             self.hca_header.block_count = u32::from_be_bytes([
                 header[header_offset + 8],
@@ -141,6 +337,9 @@ impl HCAFile {
                 header[header_offset + 0xA],
                 header[header_offset + 0xB]
             ]);
+            // Samples added before and after the audio
+            self.hca_header.encoder_delay = u16::from_be_bytes([header[header_offset + 0xC], header[header_offset + 0xD]]);
+            self.hca_header.encoder_padding = u16::from_be_bytes([header[header_offset + 0xE], header[header_offset + 0xF]]);
             header_offset += 16;
         } else {
             return Err(GICSError::new("Broken FMT header"));
@@ -152,7 +351,8 @@ impl HCAFile {
         ]) & magic;
     
         if sign == 0x706D_6F63 { // COMP
-            header.iter_mut().zip(&sign.to_le_bytes()).for_each(|(dest, source)| *dest = *source);
+            header[header_offset..].iter_mut().zip(&sign.to_le_bytes()).for_each(|(dest, source)| *dest = *source);
+            self.hca_header.chunks.push(header_offset);
             self.hca_header.block_size = u16::from_be_bytes([
                 header[header_offset + 4],
                 header[header_offset + 5]
@@ -174,7 +374,8 @@ impl HCAFile {
             }
             header_offset += 16;
         } else if sign == 0x0063_6564 {
-            header.iter_mut().zip(&sign.to_le_bytes()).for_each(|(dest, source)| *dest = *source);
+            header[header_offset..].iter_mut().zip(&sign.to_le_bytes()).for_each(|(dest, source)| *dest = *source);
+            self.hca_header.chunks.push(header_offset);
             self.hca_header.block_size = u16::from_be_bytes([
                 header[header_offset + 4],
                 header[header_offset + 5]
@@ -183,13 +384,15 @@ impl HCAFile {
             self.hca_header.comp_r02 = u32::from(header[header_offset + 0x7]);
             self.hca_header.comp_r03 = u32::from(header[header_offset + 0xA] >> 4);
             self.hca_header.comp_r04 = u32::from(header[header_offset + 0xA] & 0xF);
-            self.hca_header.comp_r05 = u32::from(header[header_offset + 0x8]);
+            // Band counts are stored minus one, and there are no stereo bands without a stereo type
+            self.hca_header.comp_r05 = u32::from(header[header_offset + 0x8]) + 1;
             self.hca_header.comp_r06 = if header[header_offset + 0xB] > 0 {
-                u32::from(header[header_offset + 0x9])
+                u32::from(header[header_offset + 0x9]) + 1
             } else {
-                u32::from(header[header_offset + 0x8] + 1)
+                self.hca_header.comp_r05
             };
-            self.hca_header.comp_r07 = self.hca_header.comp_r05 - self.hca_header.comp_r06;
+            self.hca_header.comp_r07 = self.hca_header.comp_r05.checked_sub(self.hca_header.comp_r06)
+                .ok_or_else(|| GICSError::new("invalid band counts found during HCA header reading"))?;
             self.hca_header.comp_r08 = 0;
             if !(self.hca_header.block_size >= 8 || self.hca_header.block_size == 0) {
                 return Err(GICSError::new("invalid block size found during HCA header reading"));
@@ -210,7 +413,8 @@ impl HCAFile {
             header[header_offset + 2], header[header_offset + 3]
         ]) & magic;
         if sign == 0x0072_6276 {
-            header.iter_mut().zip(sign.to_le_bytes()).for_each(|(dst, src)| *dst = src);
+            header[header_offset..].iter_mut().zip(sign.to_le_bytes()).for_each(|(dst, src)| *dst = src);
+            self.hca_header.chunks.push(header_offset);
             header_offset += 8;
         }
 
@@ -220,8 +424,9 @@ impl HCAFile {
             header[header_offset + 2], header[header_offset + 3]
         ]) & magic;
         if sign == 0x6174_6800 {
-            header.iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
-            self.hca_header.ath_type = u16::from_be_bytes([header[header_offset + 4], header[header_offset + 4]]);
+            header[header_offset..].iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
+            self.hca_header.chunks.push(header_offset);
+            self.hca_header.ath_type = u16::from_be_bytes([header[header_offset + 4], header[header_offset + 5]]);
             header_offset += 6;
         } else if self.hca_header.version < 0x200 {
             self.hca_header.ath_type = 1;
@@ -233,26 +438,44 @@ impl HCAFile {
             header[header_offset + 2], header[header_offset + 3]
         ]) & magic;
         if sign == 0x6C6F_6F70 {
-            header.iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
+            header[header_offset..].iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
+            self.hca_header.chunks.push(header_offset);
             self.hca_header.loop_flag = true;
+            // Blocks starting and ending the loop, then the samples skipped at its start and its end
+            self.hca_header.loop_start = u32::from_be_bytes([
+                header[header_offset + 4], header[header_offset + 5],
+                header[header_offset + 6], header[header_offset + 7]
+            ]);
+            self.hca_header.loop_end = u32::from_be_bytes([
+                header[header_offset + 8], header[header_offset + 9],
+                header[header_offset + 0xA], header[header_offset + 0xB]
+            ]);
+            self.hca_header.loop_start_delay = u16::from_be_bytes([header[header_offset + 0xC], header[header_offset + 0xD]]);
+            self.hca_header.loop_end_padding = u16::from_be_bytes([header[header_offset + 0xE], header[header_offset + 0xF]]);
+            if !(self.hca_header.loop_start <= self.hca_header.loop_end && self.hca_header.loop_end < self.hca_header.block_count) {
+                return Err(GICSError::new("invalid loop points found during HCA header reading"));
+            }
             header_offset += 16;
         } else {
             self.hca_header.loop_flag = false;
         }
 
-        // Cipher
+        // Cipher, whose chunk is added here to files without one when they are encrypted
+        self.hca_header.cipher_offset = header_offset;
         sign = u32::from_be_bytes([
             header[header_offset], header[header_offset + 1],
             header[header_offset + 2], header[header_offset + 3]
         ]) & magic;
         if sign == 0x6369_7068 {
-            header.iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
+            header[header_offset..].iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
+            self.hca_header.chunks.push(header_offset);
             self.hca_header.cipher_type = u16::from_be_bytes([
                 header[header_offset + 4], header[header_offset + 5]
             ]);
             if !(self.hca_header.cipher_type == 0 || self.hca_header.cipher_type == 1 || self.hca_header.cipher_type == 0x38) {
                 return Err(GICSError::new("Invalid cipher type found during HCA header reading"));
             }
+            header_offset += 6;
         } else {
             self.hca_header.cipher_type = 0;
         }
@@ -263,7 +486,8 @@ impl HCAFile {
             header[header_offset + 2], header[header_offset + 3]
         ]) & magic;
         if sign == 0x7276_6100 {
-            header.iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
+            header[header_offset..].iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
+            self.hca_header.chunks.push(header_offset);
             self.hca_header.volume = f32::from_be_bytes([
                 header[header_offset + 4], header[header_offset + 5],
                 header[header_offset + 6], header[header_offset + 7]
@@ -278,8 +502,14 @@ impl HCAFile {
             header[header_offset + 2], header[header_offset + 3]
         ]) & magic;
         if sign == 0x636F_6D6D {
-            header.iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
-            header_offset += 5;
+            header[header_offset..].iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
+            self.hca_header.chunks.push(header_offset);
+            // A length, then the comment, often ending with zeros, and room for the name of a chunk after it
+            let length = usize::from(header[header_offset + 4]);
+            let comment = header.get(header_offset + 5 .. header_offset + 5 + length + 4)
+                .ok_or_else(|| GICSError::new("invalid comment found during HCA header reading"))?;
+            self.hca_header.comment = Some(String::from_utf8_lossy(&comment[..length]).trim_end_matches('\0').into());
+            header_offset += 5 + length;
         }
 
         sign = u32::from_be_bytes([
@@ -287,15 +517,15 @@ impl HCAFile {
             header[header_offset + 2], header[header_offset + 3]
         ]) & magic;
         if sign == 0x7061_6400 {
-            header.iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
+            header[header_offset..].iter_mut().zip(sign.to_be_bytes()).for_each(|(dst, src)| *dst = src);
+            self.hca_header.chunks.push(header_offset);
             //header_offset += 4;
         }
 
-        let csum = Self::checksum(&header, header.len() - 2);
-        header.iter_mut().zip(csum.to_be_bytes()).for_each(|(dst, src)| *dst = src);
-        let data_size: usize = usize::from(self.hca_header.block_size) * self.hca_header.block_count as usize;
-        self.data = vec![0; data_size];
-        fs.read_exact(&mut self.data)?;
+        // The names are now unmasked, and the header checksum must cover them
+        let size = header.len() - 2;
+        let csum = Self::checksum(&header, size);
+        header[size..].iter_mut().zip(csum.to_be_bytes()).for_each(|(dst, src)| *dst = src);
 
         self.ath_init()?;
         self.init_mask(self.hca_header.cipher_type);
@@ -313,7 +543,7 @@ impl HCAFile {
 
     const fn reformulate(a: u32, b: u32) -> u32 {
         if b > 0 {
-            a / b + (if a % b == 0 { 0 } else { 1 })
+            a.div_ceil(b)
         } else {
             0
         }
@@ -390,25 +620,33 @@ impl HCAFile {
     }
 
     fn init_mask(&mut self, tp: u16) {
+        self.cipher_table = Self::cipher_table(tp, self.key2, self.key1);
+    }
+
+    /// Table deciphering the bytes of blocks encrypted with cipher type `tp` (0, 1 or 56), and the two halves of a key
+    fn cipher_table(tp: u16, key2: [u8; 4], key1: [u8; 4]) -> [u8; 0x100] {
+        let mut cipher_table: [u8; 0x100] = [0; 0x100];
         match tp {
-            0 => (0..0x100).for_each(|i| self.cipher_table[i] = i.try_into().unwrap()),
+            0 => (0..0x100).for_each(|i| cipher_table[i] = i.try_into().unwrap()),
             1 => {
                 let mut v: u8 = 0;
-                for i in 0..0xFF {
+                for item in &mut cipher_table[..0xFF] {
                     v = v.wrapping_mul(13).wrapping_add(11);
                     // Redo if you're on the boundary
                     if v == 0 || v == 0xFF {
                         v = v.wrapping_mul(13).wrapping_add(11);
                     }
-                    self.cipher_table[i] = v;
+                    *item = v;
                 }
-                self.cipher_table[0] = 0;
-                self.cipher_table[0xFF] = 0xFF;
+                cipher_table[0] = 0;
+                cipher_table[0xFF] = 0xFF;
             },
+            // Without a key, type 56 tables are those of type 0
+            56 if key1 == [0; 4] && key2 == [0; 4] => return Self::cipher_table(0, key2, key1),
             56 => {
                 let mut t1: [u8; 8] =  [0; 8];
-                let mut key1: u32 = u32::from_le_bytes(self.key1);
-                let mut key2: u32 = u32::from_le_bytes(self.key2);
+                let mut key1: u32 = u32::from_le_bytes(key1);
+                let mut key2: u32 = u32::from_le_bytes(key2);
 
                 if key1 == 0 {
                     key2 -= 1;
@@ -446,16 +684,17 @@ impl HCAFile {
                     v = v.wrapping_add(0x11) & 0xFF;
                     let a = t3[v];
                     if a != 0 && a != 0xFF {
-                        self.cipher_table[i_table] = a;
+                        cipher_table[i_table] = a;
                         i_table += 1;
                     }
                 }
 
-                self.cipher_table[0] = 0;
-                self.cipher_table[0xFF] = 0xFF;
+                cipher_table[0] = 0;
+                cipher_table[0xFF] = 0xFF;
             },
             _ => { }
         }
+        cipher_table
     }
 
     fn init_cipher56_table(val: u8) -> [u8; 0x10] {
@@ -471,11 +710,29 @@ impl HCAFile {
     }
 
     fn ath_init(&mut self) -> GICSResult<()> {
-        match self.hca_header.ath_type {
-            0 => {
-                self.ath_table = [0; 0x80];
-                Ok(())
-            },
+        self.ath_table = Self::ath_table(self.hca_header.ath_type, self.hca_header.sampling_rate)?;
+        Ok(())
+    }
+
+    /// Table enciphering bytes with cipher type `tp` (0, 1 or 56), the inverse of the deciphering one
+    fn encipher_table(tp: u16, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<[u8; 0x100]> {
+        let mut encipher_table: [u8; 0x100] = [0; 0x100];
+        let mut seen: [bool; 0x100] = [false; 0x100];
+        for (plain, &stored) in Self::cipher_table(tp, key2, key1).iter().enumerate() {
+            if seen[usize::from(stored)] {
+                return Err(GICSError::new("HCA cipher table cannot be inverted"));
+            }
+            seen[usize::from(stored)] = true;
+            encipher_table[usize::from(stored)] = plain as u8;
+        }
+        Ok(encipher_table)
+    }
+
+    /// Absolute threshold of hearing of every band, of ATH type `ath_type` (0 or 1)
+    fn ath_table(ath_type: u16, sampling_rate: u32) -> GICSResult<[u8; 0x80]> {
+        let mut ath_table: [u8; 0x80] = [0; 0x80];
+        match ath_type {
+            0 => Ok(ath_table),
             1 => {
                 let list: [u8; 656] = [
                     0x78, 0x5F, 0x56, 0x51, 0x4E, 0x4C, 0x4B, 0x49, 0x48, 0x48, 0x47, 0x46, 0x46, 0x45, 0x45, 0x45,
@@ -525,15 +782,13 @@ impl HCAFile {
                     let index: usize = v >> 13;
                     if index >= 0x28E {
                         // If we get above that value it means that the rest of the table is just all 0xFF
-                        for insertron in i..0x80 {
-                            self.ath_table[insertron] = 0xFF;
-                        }
-                        return Ok(());
+                        ath_table[i..].iter_mut().for_each(|item| *item = 0xFF);
+                        return Ok(ath_table);
                     }
-                    self.ath_table[i] = list[index];
-                    v += self.hca_header.sampling_rate as usize;
+                    ath_table[i] = list[index];
+                    v += sampling_rate as usize;
                 }
-                Ok(())
+                Ok(ath_table)
             },
             _ => { Err(GICSError::new("ATH table kind unknown. What kind of ATH table are you using, dear?")) }
         }
@@ -566,91 +821,107 @@ impl HCAFile {
         sum
     }
 
-    pub fn convert_to_wav(mut self, path: &Path) -> GICSResult<PathBuf> {
-        // Some definitions
-        let volume = 1.0;
-        let mode = 16;
-        let loop_flag = 0;
+    /// Decode the whole file into a PCM WAV file at `path`, along with the blocks concealed
+    pub fn convert_to_wav(self, path: &Path) -> GICSResult<(PathBuf, DamagedBlocks)> {
+        // Build a path to the wav file
+        let wav_path = PathBuf::from(path);
 
-        let mut wav_riff = WaveRiff::default();
-        wav_riff.fmt_type = if mode > 0 { 1 } else { 3 };
-        wav_riff.fmt_channel_count = self.hca_header.channel_count;
-        wav_riff.fmt_bit_count = if mode > 0 { mode } else { 32 };
-        wav_riff.fmt_sampling_rate = self.hca_header.sampling_rate;
-        wav_riff.fmt_sampling_size = wav_riff.fmt_bit_count / 8 * wav_riff.fmt_channel_count;
-        wav_riff.fmt_sampling_per_sec = wav_riff.fmt_sampling_rate * u32::from(wav_riff.fmt_sampling_size);
+        // Start to write the actual wav file
+        let mut wav_file = BufWriter::new(File::create(&wav_path)?);
+        let damaged = self.write_wav(&mut wav_file)?;
 
-        // Fill in wav sample
-        let wav_simp = WaveSample::default();
-        let mut wav_data = WaveData::default();
-        wav_data.set_data_size(self.hca_header.block_count * 0x80 * 8 * u32::from(wav_riff.fmt_sampling_size) + (wav_simp.loop_end - wav_simp.loop_start) * loop_flag);
-        wav_riff.riff_size = 0x1C + 8 + wav_data.data_size; // 8 is std::mem::size_of::<WaveData>()
+        Ok((wav_path, damaged))
+    }
 
-        // We do not consider wave samples here, Genshin does not need to have any for the HCA to WAV conversion
-        let mut header: Vec<u8> = Vec::new();
-        header.extend(wav_riff.build_byte_array());
-        header.extend(wav_data.build_byte_array());
+    /// Decode the whole file as a PCM WAV stream into `wav_file`, in the format set with [`HCAFile::set_pcm_options`].
+    ///
+    /// Blocks failing their checksum, or missing their sync word, are handled as set with
+    /// [`HCAFile::set_block_errors`], and the blocks concealed are returned
+    pub fn write_wav<W: Write>(mut self, wav_file: &mut W) -> GICSResult<DamagedBlocks> {
+        let mut data = std::mem::take(&mut self.data);
+        let mut decoder = HCAWavDecoder::new(self, wav_file)?;
+        for block in data.chunks_exact_mut(decoder.block_size()) {
+            decoder.write_block(block, wav_file)?;
+        }
+        decoder.finish(wav_file)
+    }
 
-        // Build a path to the wav file
-        let wav_path = PathBuf::from(path);
+    /// Write the stream with its blocks encrypted with cipher type `tp` (0 to decrypt it, 1 or 56) and the two halves of a
+    /// key, without decoding them.
+    ///
+    /// Chunk names are masked in encrypted streams, as encoders do. Damaged blocks are handled as set with
+    /// [`HCAFile::set_block_errors`] : concealed ones are copied as they are, for decoders to conceal them in turn
+    pub fn write_encrypted<W: Write>(&self, tp: u16, key2: [u8; 4], key1: [u8; 4], output: &mut W) -> GICSResult<DamagedBlocks> {
+        if !matches!(tp, 0 | 1 | 56) {
+            return Err(GICSError::new("HCA cipher type not supported, only types 0, 1 and 56 are"));
+        }
+        if self.hca_header.block_size < 8 {
+            return Err(GICSError::new("HCA blocks are too small to hold any sample"));
+        }
+        let encipher_table = Self::encipher_table(tp, key2, key1)?;
 
-        // Start to write the actual wav file
-        let mut wav_file: File = File::create(&wav_path)?;
-        wav_file.write_all(&header)?;
+        // The header was unmasked when read, and only its cipher type changes, in a chunk added if need be
+        let mut header = self.header.clone();
+        let mut chunks = self.hca_header.chunks.clone();
+        let cipher_offset = self.hca_header.cipher_offset;
+        if &header[cipher_offset..cipher_offset + 4] == b"ciph" {
+            header[cipher_offset + 4..cipher_offset + 6].copy_from_slice(&tp.to_be_bytes());
+        } else {
+            header.splice(cipher_offset..cipher_offset, b"ciph".iter().copied().chain(tp.to_be_bytes()));
+            chunks.iter_mut().filter(|offset| **offset >= cipher_offset).for_each(|offset| *offset += 6);
+            chunks.push(cipher_offset);
+            let data_offset = self.hca_header.data_offset + 6;
+            header[6..8].copy_from_slice(&data_offset.to_be_bytes());
+        }
+        if tp != 0 {
+            for &offset in &chunks {
+                header[offset..offset + 4].iter_mut().filter(|byte| **byte != 0).for_each(|byte| *byte |= 0x80);
+            }
+        }
+        let size = header.len() - 2;
+        let checksum = Self::checksum(&header, size);
+        header[size..].copy_from_slice(&checksum.to_be_bytes());
+        output.write_all(&header)?;
 
-        self.hca_header.volume *= volume;
-    
-        let block_size: usize = self.hca_header.block_size.try_into().unwrap();
-        let block_count: usize = self.hca_header.block_count.try_into().unwrap();
-        let mut data_2: Vec<u8> = vec![0_u8; block_size];
-        let mut offset: usize = 0;
-
-        for _ in 0..block_count {
-            // Copy <block size> bytes of data starting at offset into data 2
-            data_2.iter_mut().zip(&self.data[offset .. (offset + block_size)]).for_each(|(target, source)| *target = *source);
-            self.decode_block(&mut data_2);
-
-            for i in 0..8 {
-                for j in 0..0x80 {
-                    for k in 0..self.hca_header.channel_count {
-                        let mut f = f64::from(self.hca_channel[usize::from(k)].wave[i][j] * self.hca_header.volume);
-                        // Don't saturate
-                        if f > 1.0 { f = 1.0; }
-                        else if f < -1.0 { f = -1.0; }
-                        // Decoding mode
-                        let v = match mode {
-                            0x00 => f.trunc(), // float mode
-                            0x08 => (f * f64::from(i8::MAX)).trunc() + 128.0, // 8 bit mode
-                            0x10 => (f * f64::from(i16::MAX)).trunc(), // 16 bits
-                            0x18 => (f * f64::from(0x0080_0000 - 1)).trunc(), // 24 bits
-                            0x20 => (f * f64::from(i32::MAX)).trunc(), // 32 bits
-                            _ => {
-                                return Err(GICSError::new("Mode not supported: {}"));
-                            }
-                        } as i32;
-                        /*if f != 0.0 {
-                            println!("{} -> {}", f, v);
-                        }*/
-                        let max_bytes = usize::from(mode / 0x08);
-                        wav_file.write_all(&v.to_le_bytes()[0..max_bytes])?;
-                    }
+        let block_size = usize::from(self.hca_header.block_size);
+        let mut damaged = DamagedBlocks { blocks: Vec::new(), total: self.data.len() / block_size };
+        for (index, stored) in self.data.chunks_exact(block_size).enumerate() {
+            // Every cipher leaves the sync word as it is
+            let damage = if Self::checksum(stored, block_size) != 0 {
+                Some(BlockDamage::Checksum)
+            } else {
+                (stored[..2] != [0xFF, 0xFF]).then_some(BlockDamage::Sync)
+            };
+            if let Some(damage) = damage {
+                if self.block_errors == BlockErrors::Strict {
+                    return Err(GICSError::new(&format!("HCA block #{index} is damaged : {damage}")));
                 }
+                damaged.blocks.push((index, damage));
+                output.write_all(stored)?;
+                continue;
             }
-            offset += usize::from(self.hca_header.block_size);
+            let mut block = stored.to_vec();
+            self.mask(&mut block, block_size - 2);
+            for byte in &mut block[..block_size - 2] {
+                *byte = encipher_table[usize::from(*byte)];
+            }
+            let checksum = Self::checksum(&block, block_size - 2);
+            block[block_size - 2..].copy_from_slice(&checksum.to_be_bytes());
+            output.write_all(&block)?;
         }
-
-        // Flush and close
-        wav_file.flush()?;
-
-        Ok(wav_path)
+        output.flush()?;
+        Ok(damaged)
     }
 
-    fn decode_block(&mut self, data: &mut [u8]) {
+    /// Decrypt and decode a block, returning the number of bits read, or why it cannot be decoded.
+    ///
+    /// The samples of the channels are only replaced once the whole block is known to be decodable
+    fn decode_block(&mut self, data: &mut [u8]) -> Result<i32, BlockDamage> {
         self.mask(data, self.hca_header.block_size as usize);
-        let mut data_block: ClData = ClData::new(data.iter().map(|&u| i32::from(u)).collect(), i32::from(self.hca_header.block_size));
+        let mut data_block = ClData::new(data, i32::from(self.hca_header.block_size));
         let magic = data_block.get_bit(16);
         if magic != 0xFFFF {
-            return;
+            return Err(BlockDamage::Sync);
         }
         let a = (data_block.get_bit(9) << 8) - data_block.get_bit(7);
 
@@ -659,36 +930,478 @@ impl HCAFile {
             self.hca_channel[i as usize].decode_one(&mut data_block, self.hca_header.comp_r09, a, self.ath_table.as_ref());
         });
 
-        // Do 8 rounds of decoding
-        (0..8).for_each(|i| {
+        // Do 8 rounds of decoding. Scale factors are the same in every round, so out of range ones are found in the first,
+        // before any sample is written
+        for i in 0..8 {
             (0..channel_count).for_each(|j| self.hca_channel[usize::from(j)].decode_two(&mut data_block));
-            (0..channel_count).for_each(|j|
-                self.hca_channel[usize::from(j)]
+            for j in 0..usize::from(channel_count) {
+                self.hca_channel[j]
                     .decode_three(self.hca_header.comp_r09, self.hca_header.comp_r08,
                         self.hca_header.comp_r07 + self.hca_header.comp_r06,
                         self.hca_header.comp_r05
                     )
-            );
-            (0..channel_count-1).for_each(|j| {
-                //let chan = &mut self.hca_channel[1];
-                // I have to return here because I can't borrow the array of channels and
-                // The first channel as mutable at once
-                let former_channel = self.hca_channel[1];
-                let decoded_channel = self.hca_channel[usize::from(j)]
-                    .decode_four(i, self.hca_header.comp_r05 - self.hca_header.comp_r06,
-                            self.hca_header.comp_r06, self.hca_header.comp_r07, &former_channel);
-                self.hca_channel[1] = decoded_channel;
-            });
+                    .ok_or(BlockDamage::Scales)?;
+            }
+            // Primary channels share their stereo bands with the secondary channel after them
+            for j in 1..usize::from(channel_count) {
+                let (primaries, secondaries) = self.hca_channel.split_at_mut(j);
+                primaries[j - 1].decode_four(i, self.hca_header.comp_r05 - self.hca_header.comp_r06,
+                    self.hca_header.comp_r06, self.hca_header.comp_r07, &mut secondaries[0]);
+            }
             (0..channel_count).for_each(|j|
                 self.hca_channel[usize::from(j)].decode_five(i)
             );
-        });
+        }
+        Ok(data_block.bit)
     }
 
     fn mask(&self, data: &mut [u8], block_size: usize) {
-        (0..block_size).for_each(|i| {
-            let d = data[i];
-            data[i] = self.cipher_table[usize::from(d)];
-        });
+        for byte in &mut data[..block_size] {
+            *byte = self.cipher_table[usize::from(*byte)];
+        }
+    }
+}
+
+/// Decoder of a HCA stream into a PCM WAV stream, fed with its blocks one at a time
+pub struct HCAWavDecoder {
+    file: HCAFile,
+    loop_points: Option<(u32, u32)>,
+    loop_count: u32,
+    repeats: u32,
+    loop_samples: Vec<f64>,
+    position: u32,
+    index: usize,
+    damaged: DamagedBlocks,
+    pcm: Vec<u8>
+}
+
+impl HCAWavDecoder {
+    /// Write the WAV header of `file` into `wav_file`, in the format set with [`HCAFile::set_pcm_options`]
+    ///
+    /// # Panics
+    /// If the block count does not fit in a `usize`
+    pub fn new<W: Write>(mut file: HCAFile, wav_file: &mut W) -> GICSResult<Self> {
+        if file.hca_header.block_size < 8 {
+            return Err(GICSError::new("HCA blocks are too small to hold any sample"));
+        }
+        let mut wav_riff = WaveRiff::for_format(file.pcm.format, file.hca_header.channel_count, file.hca_header.sampling_rate);
+
+        // Loops are played `loop_count` times, and marked at their last iteration for samplers to go on looping
+        let loop_points = file.loop_points();
+        let loop_count = file.loop_count.max(1);
+        // Sizes are written on 32 bits, which too many loops overflow
+        let too_long = || GICSError::new("Decoded audio too long for a WAV file, try fewer loops");
+        let repeats = loop_points.map_or(Some(0), |(start, end)| (end - start).checked_mul(loop_count - 1)).ok_or_else(too_long)?;
+        let samples = file.hca_header.block_count.checked_mul(0x80 * 8).and_then(|samples| samples.checked_add(repeats)).ok_or_else(too_long)?;
+        let mut wav_data = WaveData::default();
+        wav_data.set_data_size(samples.checked_mul(u32::from(wav_riff.fmt_sampling_size)).ok_or_else(too_long)?);
+        let wav_smpl = loop_points.map(|(start, end)| end.checked_add(repeats)
+            .map(|end| WaveSample::for_loop(file.hca_header.sampling_rate, start + repeats, end)).ok_or_else(too_long))
+            .transpose()?;
+        wav_riff.riff_size = (wav_riff.header_size() + wav_smpl.as_ref().map_or(0, |smpl| 8 + smpl.smpl_size) + 8) // 8 is std::mem::size_of::<WaveData>()
+            .checked_add(wav_data.data_size).ok_or_else(too_long)?;
+
+        let mut header: Vec<u8> = Vec::new();
+        header.extend(wav_riff.build_byte_array());
+        if let Some(smpl) = wav_smpl {
+            header.extend(smpl.build_byte_array());
+        }
+        header.extend(wav_data.build_byte_array());
+
+        wav_file.write_all(&header)?;
+
+        file.hca_header.volume *= file.pcm.gain;
+        let block_count: usize = file.hca_header.block_count.try_into().unwrap();
+        Ok(Self {
+            file,
+            loop_points,
+            loop_count,
+            repeats,
+            loop_samples: Vec::new(),
+            position: 0,
+            index: 0,
+            damaged: DamagedBlocks { blocks: Vec::new(), total: block_count },
+            // The samples of every block are gathered, and written at once
+            pcm: Vec::with_capacity(8 * 0x80 * usize::from(wav_riff.fmt_sampling_size))
+        })
+    }
+
+    /// Size of the blocks of the stream
+    #[must_use]
+    pub fn block_size(&self) -> usize {
+        usize::from(self.file.hca_header.block_size)
+    }
+
+    /// Decode the next block of the stream in place, and write its samples into `wav_file`.
+    ///
+    /// Blocks failing their checksum, or missing their sync word, are handled as set with
+    /// [`HCAFile::set_block_errors`]. Blocks past the count of the header are ignored
+    pub fn write_block<W: Write>(&mut self, block: &mut [u8], wav_file: &mut W) -> GICSResult<()> {
+        let index = self.index;
+        if index >= self.damaged.total {
+            return Ok(());
+        }
+        let block_size = self.block_size();
+        if block.len() != block_size {
+            return Err(GICSError::new(&format!("HCA block #{index} is {} bytes long instead of {block_size}", block.len())));
+        }
+        self.index += 1;
+        // The checksum covers the block as stored, encrypted or not
+        let damage = if HCAFile::checksum(block, block_size) != 0 {
+            Some(BlockDamage::Checksum)
+        } else {
+            self.file.decode_block(block).err()
+        };
+        if let Some(damage) = damage {
+            if self.file.block_errors == BlockErrors::Strict {
+                return Err(GICSError::new(&format!("HCA block #{index} is damaged : {damage}")));
+            }
+            self.damaged.blocks.push((index, damage));
+        }
+        // Damaged blocks are not decoded, leaving the samples of the last block in place to be repeated
+        let silent = damage.is_some() && self.file.block_errors == BlockErrors::Silence;
+
+        let format = self.file.pcm.format;
+        let channel_count = usize::from(self.file.hca_header.channel_count);
+        let volume = self.file.hca_header.volume;
+        let (repeats, loop_points) = (self.repeats, self.loop_points);
+        self.pcm.clear();
+        for i in 0..8 {
+            for j in 0..0x80 {
+                let looped = repeats > 0 && loop_points.is_some_and(|(start, end)| (start..end).contains(&self.position));
+                for channel in &self.file.hca_channel[..channel_count] {
+                    let f = if silent {
+                        0.0
+                    } else {
+                        f64::from(channel.wave[i][j] * volume)
+                    };
+                    format.push_sample(f, &mut self.pcm);
+                    if looped {
+                        self.loop_samples.push(f);
+                    }
+                }
+                self.position += 1;
+                // The loop is played again as soon as its first iteration is over
+                if repeats > 0 && loop_points.is_some_and(|(_, end)| end == self.position) {
+                    for _ in 1..self.loop_count {
+                        for &f in &self.loop_samples {
+                            format.push_sample(f, &mut self.pcm);
+                        }
+                    }
+                }
+            }
+        }
+        wav_file.write_all(&self.pcm)?;
+        Ok(())
+    }
+
+    /// Flush `wav_file` once every block of the stream is written, and return the blocks concealed
+    pub fn finish<W: Write>(self, wav_file: &mut W) -> GICSResult<DamagedBlocks> {
+        if self.index < self.damaged.total {
+            return Err(GICSError::new(&format!("HCA stream ends after {} of its {} blocks", self.index, self.damaged.total)));
+        }
+        wav_file.flush()?;
+        Ok(self.damaged)
+    }
+}
+
+/// Header of a HCA file : its layout, compression, cipher and metadata
+#[derive(Clone, Debug, Serialize)]
+pub struct HCAProbe {
+    /// Name of the probed file
+    pub filename: String,
+    /// Size of the probed file
+    pub size: u64,
+    /// Version of the header, as `major.minor`
+    pub version: String,
+    /// Size of the header, at which the blocks start
+    pub data_offset: u16,
+    /// Number of channels
+    pub channel_count: u16,
+    /// Sampling rate, in Hz
+    pub sampling_rate: u32,
+    /// Number of blocks, of 1024 samples of every channel
+    pub block_count: u32,
+    /// Size of every block
+    pub block_size: u16,
+    /// Samples added by the encoder before the audio
+    pub encoder_delay: u16,
+    /// Samples added by the encoder after the audio, to fill the last block
+    pub encoder_padding: u16,
+    /// Samples of every channel, without those added by the encoder
+    pub samples: u64,
+    /// Duration of the audio, in seconds
+    pub duration: f64,
+    /// Parameters of the `comp` or `dec` chunk, `comp_r01` to `comp_r09`
+    pub compression: [u32; 9],
+    /// Type of the absolute threshold of hearing
+    pub ath_type: u16,
+    /// Cipher type : 0 for plain files, 1 for the keyless cipher, and 56 for the keyed one
+    pub cipher_type: u16,
+    /// Whether the blocks are encrypted
+    pub encrypted: bool,
+    /// First sample of the loop and sample after its end, for looping files, counted like `samples` from the end of the
+    /// encoder delay
+    pub loop_points: Option<(u32, u32)>,
+    /// Volume of the `rva` chunk (1 without one)
+    pub volume: f32,
+    /// Comment of the `comm` chunk
+    pub comment: Option<String>
+}
+
+impl HCAProbe {
+    /// Read the header of the HCA file at `path`, without decoding anything
+    pub fn from_path(path: &Path) -> GICSResult<Self> {
+        let filename: String = path
+            .file_name().ok_or_else(|| GICSError::new("HCA Path has no file name"))?
+            .to_string_lossy().into();
+        Self::from_reader(filename, BufReader::new(File::open(path)?))
+    }
+
+    /// Read the header of a HCA file from any seekable source
+    pub fn from_reader<R: Read + Seek>(filename: String, mut reader: R) -> GICSResult<Self> {
+        let start = reader.stream_position()?;
+        let size = reader.seek(SeekFrom::End(0))? - start;
+        reader.seek(SeekFrom::Start(start))?;
+
+        let mut file = HCAFile::blank([0; 4], [0; 4]);
+        file.read_header(&mut reader)?;
+        let header_delay = u32::from(file.hca_header.encoder_delay);
+        let loop_points = file.loop_points()
+            .map(|(start, end)| (start.saturating_sub(header_delay), end.saturating_sub(header_delay)));
+        let header = file.hca_header;
+        let samples = (u64::from(header.block_count) * 0x400)
+            .saturating_sub(u64::from(header.encoder_delay) + u64::from(header.encoder_padding));
+        Ok(Self {
+            filename,
+            size,
+            version: format!("{}.{}", header.version >> 8, header.version & 0xFF),
+            data_offset: header.data_offset,
+            channel_count: header.channel_count,
+            sampling_rate: header.sampling_rate,
+            block_count: header.block_count,
+            block_size: header.block_size,
+            encoder_delay: header.encoder_delay,
+            encoder_padding: header.encoder_padding,
+            samples,
+            duration: f64::from(header.block_count).mul_add(1024.0, -f64::from(header.encoder_delay) - f64::from(header.encoder_padding))
+                .max(0.0) / f64::from(header.sampling_rate.max(1)),
+            compression: [
+                header.comp_r01, header.comp_r02, header.comp_r03, header.comp_r04, header.comp_r05,
+                header.comp_r06, header.comp_r07, header.comp_r08, header.comp_r09
+            ],
+            ath_type: header.ath_type,
+            cipher_type: header.cipher_type,
+            encrypted: header.cipher_type != 0,
+            loop_points,
+            volume: header.volume,
+            comment: header.comment
+        })
+    }
+}
+
+impl Display for HCAProbe {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(fmt, "{} ({} bytes)", self.filename, self.size)?;
+        writeln!(fmt, "  HCA {}, {} channels, {} Hz, {} blocks of {} bytes from offset {}", self.version, self.channel_count,
+            self.sampling_rate, self.block_count, self.block_size, self.data_offset)?;
+        writeln!(fmt, "  {} samples ({:.3}s), encoder delay {}, padding {}", self.samples, self.duration, self.encoder_delay,
+            self.encoder_padding)?;
+        writeln!(fmt, "  Compression : {}", self.compression.map(|r| r.to_string()).join(", "))?;
+        writeln!(fmt, "  ATH type {}, cipher type {} ({})", self.ath_type, self.cipher_type,
+            if self.encrypted { "encrypted" } else { "not encrypted" })?;
+        if let Some((start, end)) = self.loop_points {
+            writeln!(fmt, "  Loop : samples {start} to {end}")?;
+        }
+        writeln!(fmt, "  Volume : {}", self.volume)?;
+        if let Some(comment) = &self.comment {
+            writeln!(fmt, "  Comment : \"{comment}\"")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod hca_tests {
+    use super::*;
+    use super::hca_encoder_tests::wav;
+
+    pub const KEY2: u32 = 0x0BCD_EF01;
+    pub const KEY1: u32 = 0x00A1_2345;
+
+    /// A HCA file encoded with `options` from a chord of sines
+    pub fn encoded(options: HCAEncoderOptions, channel_count: u16, frames: u32) -> Vec<u8> {
+        let mut hca: Vec<u8> = Vec::new();
+        HCAEncoder::new(options).unwrap().encode(Cursor::new(wav(channel_count, frames).0), &mut hca).unwrap();
+        hca
+    }
+
+    /// Options of files encrypted with the type 56 cipher, and the test key
+    pub fn encrypted() -> HCAEncoderOptions {
+        HCAEncoderOptions { cipher_type: Some(56), key2: KEY2, key1: KEY1, ..Default::default() }
+    }
+
+    /// Offset of the chunk named `name` in the header of `hca`, masked or not
+    pub fn chunk(hca: &[u8], name: [u8; 4]) -> usize {
+        hca.windows(4).position(|window| window.iter().map(|byte| byte & 0x7F).eq(name.iter().copied())).unwrap()
+    }
+
+    /// Compute the checksum of the header of `hca` again
+    pub fn seal_header(hca: &mut [u8]) {
+        let size = usize::from(u16::from_be_bytes([hca[6], hca[7]])) - 2;
+        let checksum = HCAFile::checksum(hca, size);
+        hca[size..size + 2].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    #[test]
+    fn wrong_keys_mismatch_with_high_frequency_bands() {
+        // Bands past the coded ones are rebuilt in groups of 4 : the scale factors read for them with a wrong key used
+        // to index their table out of bounds
+        let mut hca = encoded(HCAEncoderOptions { quality: HCAQuality::Lowest, ..encrypted() }, 2, 0x4000);
+        let comp = chunk(&hca, *b"comp");
+        hca[comp + 0xA] = 0x80;
+        hca[comp + 0xD] = 4;
+        seal_header(&mut hca);
+
+        let mut key: u64 = 0x1234_5678_9ABC_DEF0;
+        for _ in 0..200 {
+            key = key.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            let (key2, key1) = ((key >> 32) as u32, key as u32);
+            let mut file = HCAFile::from_reader(&mut hca.as_slice(), key2.to_le_bytes(), key1.to_le_bytes()).unwrap();
+            assert_eq!(file.hca_header.comp_r09, 12);
+            assert_ne!(file.verify_key(KEY_CHECK_BLOCKS).unwrap(), KeyCheck::Valid, "key ({key2:08X}, {key1:08X})");
+        }
+    }
+
+    #[test]
+    fn right_key_is_valid() {
+        let hca = encoded(encrypted(), 2, 0x4000);
+        let mut file = HCAFile::from_reader(&mut hca.as_slice(), KEY2.to_le_bytes(), KEY1.to_le_bytes()).unwrap();
+        assert_eq!(file.verify_key(KEY_CHECK_BLOCKS).unwrap(), KeyCheck::Valid);
+        let mut file = HCAFile::from_reader(&mut hca.as_slice(), KEY1.to_le_bytes(), KEY2.to_le_bytes()).unwrap();
+        assert_eq!(file.verify_key(KEY_CHECK_BLOCKS).unwrap(), KeyCheck::Mismatch);
+    }
+
+    /// PCM data of the WAV file `hca` decodes to with `block_errors`, or the error stopping it
+    fn decoded(hca: &[u8], block_errors: BlockErrors) -> GICSResult<(Vec<u8>, DamagedBlocks)> {
+        let mut file = HCAFile::from_reader(&mut &hca[..], [0; 4], [0; 4])?;
+        file.set_block_errors(block_errors);
+        let mut output: Vec<u8> = Vec::new();
+        let damaged = file.write_wav(&mut output)?;
+        let data = output.windows(4).position(|window| window == b"data").unwrap() + 8;
+        Ok((output.split_off(data), damaged))
+    }
+
+    /// PCM samples of the block `index` of `pcm`, at 0x400 frames of 2 channels of 16 bits per block
+    fn pcm_block(pcm: &[u8], index: usize) -> &[u8] {
+        &pcm[index * 0x1000..(index + 1) * 0x1000]
+    }
+
+    #[test]
+    fn damaged_blocks() {
+        let mut hca = encoded(HCAEncoderOptions::default(), 2, 0x4000);
+        let header = HCAFile::from_reader(&mut hca.as_slice(), [0; 4], [0; 4]).unwrap().hca_header;
+        let (clean, _) = decoded(&hca, BlockErrors::Strict).unwrap();
+        hca[usize::from(header.data_offset) + 3 * usize::from(header.block_size) + 0x10] ^= 0x5A;
+
+        let error = decoded(&hca, BlockErrors::Strict).unwrap_err();
+        assert_eq!(error.to_string(), "HCA block #3 is damaged : bad checksum");
+
+        let (silence, report) = decoded(&hca, BlockErrors::Silence).unwrap();
+        assert_eq!(report, DamagedBlocks { blocks: vec![(3, BlockDamage::Checksum)], total: header.block_count as usize });
+        assert_eq!(report.to_string(), format!("1 of {} blocks damaged : #3 (bad checksum)", header.block_count));
+        assert_eq!(silence.len(), clean.len());
+        assert_eq!(silence[..3 * 0x1000], clean[..3 * 0x1000]);
+        assert!(pcm_block(&clean, 3).iter().any(|&byte| byte != 0));
+        assert!(pcm_block(&silence, 3).iter().all(|&byte| byte == 0));
+
+        let (repeat, report) = decoded(&hca, BlockErrors::Repeat).unwrap();
+        assert_eq!(report.blocks, [(3, BlockDamage::Checksum)]);
+        assert_eq!(pcm_block(&repeat, 3), pcm_block(&clean, 2));
+    }
+
+    /// Whether the header and every block of `hca` pass their checksums
+    fn checksums_hold(hca: &[u8]) -> bool {
+        let header = HCAFile::from_reader(&mut &hca[..], [0; 4], [0; 4]).unwrap().hca_header;
+        let (data_offset, block_size) = (usize::from(header.data_offset), usize::from(header.block_size));
+        HCAFile::checksum(hca, data_offset) == 0
+            && hca[data_offset..].chunks(block_size).all(|block| HCAFile::checksum(block, block_size) == 0)
+    }
+
+    /// `hca` rewritten with `cipher_type` and the test key, after being decrypted with `key2` and `key1`
+    fn rewritten(hca: &[u8], key2: u32, key1: u32, cipher_type: u16) -> Vec<u8> {
+        let file = HCAFile::from_reader(&mut &hca[..], key2.to_le_bytes(), key1.to_le_bytes()).unwrap();
+        let mut output: Vec<u8> = Vec::new();
+        assert!(file.write_encrypted(cipher_type, KEY2.to_le_bytes(), KEY1.to_le_bytes(), &mut output).unwrap().is_empty());
+        output
+    }
+
+    #[test]
+    fn encryption_round_trip() {
+        let plain = encoded(HCAEncoderOptions { cipher_type: Some(0), ..Default::default() }, 2, 0x4000);
+        let enciphered = rewritten(&plain, 0, 0, 56);
+        assert_ne!(enciphered, plain);
+        assert!(checksums_hold(&enciphered));
+        let mut file = HCAFile::from_reader(&mut enciphered.as_slice(), KEY2.to_le_bytes(), KEY1.to_le_bytes()).unwrap();
+        assert_eq!(file.verify_key(KEY_CHECK_BLOCKS).unwrap(), KeyCheck::Valid);
+        // The encoder enciphers blocks the same way
+        assert_eq!(enciphered, encoded(encrypted(), 2, 0x4000));
+
+        let decrypted = rewritten(&enciphered, KEY2, KEY1, 0);
+        assert!(checksums_hold(&decrypted));
+        assert_eq!(decrypted, plain);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn file_encryption_round_trip() {
+        use crate::demux::{decrypt_hca, encrypt_hca, DemuxOptions};
+
+        let plain = encoded(HCAEncoderOptions { cipher_type: Some(0), ..Default::default() }, 1, 0x2000);
+        let directory = std::env::temp_dir().join(format!("gics_hca_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let (input, encrypted, decrypted) = (directory.join("plain.hca"), directory.join("encrypted.hca"), directory.join("decrypted.hca"));
+        std::fs::write(&input, &plain).unwrap();
+
+        let options = DemuxOptions::default();
+        encrypt_hca(&input, KEY2, KEY1, &encrypted, &options).unwrap();
+        assert!(encrypt_hca(&encrypted, KEY2, KEY1, &decrypted, &options).is_err());
+        assert!(decrypt_hca(&encrypted, KEY1, KEY2, &decrypted, &options).is_err());
+        decrypt_hca(&encrypted, KEY2, KEY1, &decrypted, &options).unwrap();
+        let output = std::fs::read(&decrypted).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(output, plain);
+    }
+
+    #[test]
+    fn looped_wav() {
+        let mut hca = encoded(HCAEncoderOptions { cipher_type: Some(0), ..Default::default() }, 2, 0x4000);
+        // Loop from the sample 0x20 of block 2 to the sample 0x40 before the end of block 5
+        let ciph = chunk(&hca, *b"ciph");
+        hca.splice(ciph..ciph, b"loop\0\0\0\x02\0\0\0\x05\0\x20\0\x40".iter().copied());
+        let data_offset = u16::from_be_bytes([hca[6], hca[7]]) + 0x10;
+        hca[6..8].copy_from_slice(&data_offset.to_be_bytes());
+        seal_header(&mut hca);
+
+        let mut file = HCAFile::from_reader(&mut hca.as_slice(), [0; 4], [0; 4]).unwrap();
+        let (start, end) = (0x820, 0x17C0);
+        assert_eq!(file.loop_points(), Some((start, end)));
+        let samples = file.hca_header.block_count * 0x400 + 2 * (end - start);
+        file.set_loop_count(3);
+        let mut wav: Vec<u8> = Vec::new();
+        assert!(file.write_wav(&mut wav).unwrap().is_empty());
+
+        // The sampler loops over the last iteration of the loop
+        let smpl = wav.windows(4).position(|window| window == b"smpl").unwrap();
+        let repeats = 2 * (end - start);
+        assert_eq!(wav[smpl + 0x34..smpl + 0x3C], [(start + repeats).to_le_bytes(), (end + repeats - 1).to_le_bytes()].concat());
+        let data = wav.windows(4).position(|window| window == b"data").unwrap();
+        assert_eq!(wav[data + 4..data + 8], (samples * 4).to_le_bytes());
+        assert_eq!(wav[4..8], (wav.len() as u32 - 8).to_le_bytes());
+        let pcm = &wav[data + 8..];
+        assert_eq!(pcm.len(), samples as usize * 4);
+        let iteration = |index: u32| &pcm[((start + index * (end - start)) * 4) as usize..((end + index * (end - start)) * 4) as usize];
+        assert_eq!(iteration(0), iteration(1));
+        assert_eq!(iteration(1), iteration(2));
+        assert_ne!(iteration(0), &pcm[..((end - start) * 4) as usize]);
+    }
+}
@@ -0,0 +1,497 @@
+/// Version of the HCA files written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HCAVersion {
+    /// Version 2.0
+    #[default]
+    V2_0,
+    /// Version 3.0
+    V3_0
+}
+
+impl HCAVersion {
+    const fn number(self) -> u16 {
+        match self {
+            Self::V2_0 => 0x0200,
+            Self::V3_0 => 0x0300
+        }
+    }
+}
+
+/// Quality of the HCA files written, as a bitrate and a bandwidth
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HCAQuality {
+    /// About 128 kbps per channel at 48 kHz, over the whole band
+    Highest,
+    /// About 100 kbps per channel at 48 kHz, over the whole band
+    #[default]
+    High,
+    /// About 78 kbps per channel at 48 kHz, up to 21 kHz
+    Middle,
+    /// About 63 kbps per channel at 48 kHz, up to 18 kHz
+    Low,
+    /// About 48 kbps per channel at 48 kHz, up to 15 kHz
+    Lowest
+}
+
+impl HCAQuality {
+    /// Bytes given to each channel in a block, and number of bands coded
+    const fn layout(self) -> (u16, u32) {
+        match self {
+            Self::Highest => (0x155, 0x80),
+            Self::High => (0x110, 0x80),
+            Self::Middle => (0xD0, 0x70),
+            Self::Low => (0xA8, 0x60),
+            Self::Lowest => (0x80, 0x50)
+        }
+    }
+}
+
+/// Options of the HCA files written
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HCAEncoderOptions {
+    /// Version of the header
+    pub version: HCAVersion,
+    /// Describe the compression with a `dec` chunk, as older files do, rather than a `comp` chunk
+    pub dec_chunk: bool,
+    /// Type (0 or 1) of the absolute threshold of hearing, written in an `ath` chunk if set (type 0 otherwise)
+    pub ath_type: Option<u16>,
+    /// Type (0, 1 or 56) of the cipher, written in a `ciph` chunk if set (type 0 otherwise)
+    pub cipher_type: Option<u16>,
+    /// Higher half of the key of type 56 ciphers
+    pub key2: u32,
+    /// Lower half of the key of type 56 ciphers
+    pub key1: u32,
+    /// Bitrate and bandwidth
+    pub quality: HCAQuality
+}
+
+/// Samples inserted before the audio, for the first subframe to overlap silence
+const HCA_ENCODER_DELAY: u32 = 0x80;
+
+/// Writer of the big-endian bit fields of HCA blocks
+struct BitWriter {
+    data: Vec<u8>,
+    bit: usize
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        for i in (0..bits).rev() {
+            if self.bit >> 3 == self.data.len() {
+                self.data.push(0);
+            }
+            if value >> i & 1 == 1 {
+                self.data[self.bit >> 3] |= 0x80 >> (self.bit & 7);
+            }
+            self.bit += 1;
+        }
+    }
+}
+
+/// Spectrum of one channel over a block, and the scale factor of every band
+struct BlockSpectrum {
+    coefficients: [[f32; 0x80]; 8],
+    scales: [u8; 0x80]
+}
+
+/// Encoder of PCM WAV files into HCA files
+pub struct HCAEncoder {
+    options: HCAEncoderOptions,
+    /// Table enciphering the bytes of blocks, the inverse of the deciphering one
+    cipher_table: [u8; 0x100],
+    /// Size in bits and bits of the code of every value, for every resolution below 8
+    codes: [[(u8, u16); 15]; 8]
+}
+
+impl HCAEncoder {
+    /// Prepare the encoding of HCA files with `options`
+    pub fn new(options: HCAEncoderOptions) -> GICSResult<Self> {
+        if !matches!(options.ath_type, None | Some(0 | 1)) {
+            return Err(GICSError::new("HCA ATH type not supported, only types 0 and 1 are"));
+        }
+        let cipher_type = options.cipher_type.unwrap_or(0);
+        if !matches!(cipher_type, 0 | 1 | 56) {
+            return Err(GICSError::new("HCA cipher type not supported, only types 0, 1 and 56 are"));
+        }
+        let cipher_table = HCAFile::encipher_table(cipher_type, options.key2.to_le_bytes(), options.key1.to_le_bytes())?;
+
+        // Codes are the shortest prefixes read back as each value
+        let mut codes: [[(u8, u16); 15]; 8] = [[(0, 0); 15]; 8];
+        for resolution in 1..8 {
+            let bits = QUANTIZE_BITS[resolution] as u8;
+            for read in (0..1_u16 << bits).rev() {
+                let index = resolution << 4 | usize::from(read);
+                let size = QUANTIZE_CODE_BITS[index] as u8;
+                codes[resolution][(QUANTIZE_VALUES[index] as i32 + 7) as usize] = (size, read >> (bits - size));
+            }
+        }
+        Ok(Self { options, cipher_table, codes })
+    }
+
+    /// Encode the WAV file at `wav` into a HCA file at `path`
+    pub fn encode_file(&self, wav: &Path, path: &Path) -> GICSResult<PathBuf> {
+        let mut output = BufWriter::new(File::create(path)?);
+        self.encode(BufReader::new(File::open(wav)?), &mut output)?;
+        Ok(PathBuf::from(path))
+    }
+
+    /// Encode a PCM WAV stream from any seekable source into `output`
+    pub fn encode<R: Read + Seek, W: Write>(&self, wav: R, output: &mut W) -> GICSResult<()> {
+        let mut wav = WaveReader::new(wav)?;
+        let channel_count = usize::from(wav.channel_count);
+        if channel_count == 0 || channel_count > 0x10 {
+            return Err(GICSError::new("HCA files hold between 1 and 16 channels"));
+        }
+        if wav.sampling_rate == 0 || wav.sampling_rate > 0xFF_FFFF {
+            return Err(GICSError::new("Sampling rate not supported by HCA files"));
+        }
+        let (channel_size, band_count) = self.options.quality.layout();
+        let block_size = channel_size * wav.channel_count;
+        let frames = u32::try_from(wav.frame_count()).ok().filter(|&frames| frames <= u32::MAX - 0x800)
+            .ok_or_else(|| GICSError::new("WAV file too long for a HCA file"))?;
+        let block_count = (frames + HCA_ENCODER_DELAY).div_ceil(0x400);
+        let ath_table = HCAFile::ath_table(self.options.ath_type.unwrap_or(0), wav.sampling_rate)?;
+
+        output.write_all(&self.header(wav.channel_count, wav.sampling_rate, block_count, frames, block_size, band_count))?;
+
+        let mut channel = Channel::new();
+        let mut spectra: Vec<BlockSpectrum> = Vec::with_capacity(channel_count);
+        for block in 0..block_count {
+            // Every block overlaps the first subframe of the next one
+            let start = i64::from(block) * 0x400 - i64::from(HCA_ENCODER_DELAY);
+            let skipped = usize::try_from(-start).unwrap_or(0);
+            let read = wav.read_samples(u64::try_from(start).unwrap_or(0), (0x480 - skipped) as u64)?;
+            spectra.clear();
+            for index in 0..channel_count {
+                let mut samples: [f32; 0x480] = [0.0; 0x480];
+                read.iter().skip(index).step_by(channel_count).zip(&mut samples[skipped..]).for_each(|(&sample, target)| *target = sample);
+                spectra.push(Self::spectrum(&mut channel, &samples));
+            }
+            output.write_all(&self.encode_block(&spectra, &ath_table, usize::from(block_size), band_count as usize))?;
+        }
+        output.flush()?;
+        Ok(())
+    }
+
+    /// Header of a file of `block_count` blocks holding `frames` samples of every channel
+    fn header(&self, channel_count: u16, sampling_rate: u32, block_count: u32, frames: u32, block_size: u16, band_count: u32) -> Vec<u8> {
+        // Chunk names are masked in encrypted files
+        let masked = self.options.cipher_type.unwrap_or(0) != 0;
+        let name = |name: &[u8; 4]| name.map(|byte| if masked && byte != 0 { byte | 0x80 } else { byte });
+        let bands = band_count as u8;
+
+        let mut chunks: Vec<u8> = Vec::new();
+        chunks.extend(name(b"fmt\0"));
+        chunks.push(channel_count as u8);
+        chunks.extend(&sampling_rate.to_be_bytes()[1..]);
+        chunks.extend(block_count.to_be_bytes());
+        chunks.extend((HCA_ENCODER_DELAY as u16).to_be_bytes());
+        chunks.extend(((block_count * 0x400 - frames - HCA_ENCODER_DELAY) as u16).to_be_bytes());
+        if self.options.dec_chunk {
+            // Band counts minus one, one track, and no stereo bands
+            chunks.extend(name(b"dec\0"));
+            chunks.extend(block_size.to_be_bytes());
+            chunks.extend([1, 15, bands - 1, bands - 1, 0x10, 0]);
+        } else {
+            // One track, with only base bands and no high frequency reconstruction
+            chunks.extend(name(b"comp"));
+            chunks.extend(block_size.to_be_bytes());
+            chunks.extend([1, 15, 1, 0, bands, bands, 0, 0, 0, 0]);
+        }
+        if let Some(ath_type) = self.options.ath_type {
+            chunks.extend(name(b"ath\0"));
+            chunks.extend(ath_type.to_be_bytes());
+        }
+        if let Some(cipher_type) = self.options.cipher_type {
+            chunks.extend(name(b"ciph"));
+            chunks.extend(cipher_type.to_be_bytes());
+        }
+        chunks.extend(name(b"pad\0"));
+
+        let mut header: Vec<u8> = Vec::new();
+        header.extend(name(b"HCA\0"));
+        header.extend(self.options.version.number().to_be_bytes());
+        header.extend(((chunks.len() + 10) as u16).to_be_bytes());
+        header.extend(chunks);
+        let checksum = HCAFile::checksum(&header, header.len());
+        header.extend(checksum.to_be_bytes());
+        header
+    }
+
+    /// Spectrum of the 8 subframes of a block, from its samples and those of the first subframe of the next block
+    fn spectrum(channel: &mut Channel, samples: &[f32; 0x480]) -> BlockSpectrum {
+        let window: [[f32; 0x40]; 2] = WINDOW_INT.map(|half| half.map(f32::from_bits));
+        let mut coefficients: [[f32; 0x80]; 8] = [[0.0; 0x80]; 8];
+        for (subframe, target) in coefficients.iter_mut().enumerate() {
+            // Fold the two subframes overlapped by the window, the way the decoder unfolds them
+            let current = &samples[subframe * 0x80 .. subframe * 0x80 + 0x80];
+            let next = &samples[subframe * 0x80 + 0x80 .. subframe * 0x80 + 0x100];
+            for j in 0..0x40 {
+                channel.block[0x40 + j] = window[0][j].mul_add(current[j], window[1][0x3F - j] * current[0x7F - j]);
+                channel.block[j] = window[1][j].mul_add(next[0x3F - j], -window[0][0x3F - j] * next[0x40 + j]);
+            }
+            channel.transform();
+            *target = channel.wav2;
+        }
+
+        // The scale factor of a band is the smallest covering all of its coefficients
+        let mut scales: [u8; 0x80] = [0; 0x80];
+        for (band, scale) in scales.iter_mut().enumerate() {
+            let peak = coefficients.iter().map(|subframe| subframe[band].abs()).fold(0.0, f32::max);
+            if peak > f32::from_bits(VALUE_INT[0]) / 2.0 {
+                *scale = (1..0x3F).find(|&index| f32::from_bits(VALUE_INT[index]) >= peak).unwrap_or(0x3F) as u8;
+            }
+        }
+        BlockSpectrum { coefficients, scales }
+    }
+
+    /// Difference between the noise level of a band and its scale factor, from which the decoder picks its resolution
+    fn level(scale: u8, band: usize, noise: i32, ath_table: &[u8; 0x80]) -> i32 {
+        i32::from(ath_table[band]) + ((noise + i32::from(band as u8)) >> 8) - i32::from(scale) * 5 / 2 + 1
+    }
+
+    /// Resolution of a band, from its scale factor and the noise level of the block, as the decoder computes it
+    fn resolution(scale: u8, band: usize, noise: i32, ath_table: &[u8; 0x80]) -> usize {
+        if scale == 0 {
+            return 0;
+        }
+        match Self::level(scale, band, noise, ath_table) {
+            v if v < 0 => 15,
+            v if v >= 0x39 => 1,
+            v => usize::from(SCALE_LIST[v as usize])
+        }
+    }
+
+    /// Scale factors written at a noise level : bands under it would still cost a bit per coefficient, and are left out
+    fn scales_at(spectrum: &BlockSpectrum, noise: i32, ath_table: &[u8; 0x80], band_count: usize) -> [u8; 0x80] {
+        let mut scales: [u8; 0x80] = [0; 0x80];
+        for (band, scale) in scales[..band_count].iter_mut().enumerate() {
+            let original = spectrum.scales[band];
+            if original != 0 && Self::level(original, band, noise, ath_table) < 0x39 {
+                *scale = original;
+            }
+        }
+        scales
+    }
+
+    /// Quantized value of a coefficient
+    fn quantize(coefficient: f32, scale: u8, resolution: usize) -> i32 {
+        if resolution == 0 {
+            return 0;
+        }
+        let step = f32::from_bits(VALUE_INT[usize::from(scale)]) * f32::from_bits(SCALE_INT[resolution]);
+        let max = if resolution < 8 { i32::from(resolution as u8) } else { (1 << (QUANTIZE_BITS[resolution] - 1)) - 1 };
+        ((coefficient / step).round() as i32).clamp(-max, max)
+    }
+
+    /// Size of the code of a quantized value, in bits
+    fn code_size(&self, value: i32, resolution: usize) -> u32 {
+        match resolution {
+            0 => 0,
+            1..=7 => u32::from(self.codes[resolution][(value + 7) as usize].0),
+            // Zero has no sign bit
+            _ => QUANTIZE_BITS[resolution] as u32 - u32::from(value == 0)
+        }
+    }
+
+    fn write_code(&self, writer: &mut BitWriter, value: i32, resolution: usize) {
+        match resolution {
+            0 => {},
+            1..=7 => {
+                let (size, code) = self.codes[resolution][(value + 7) as usize];
+                writer.write(u32::from(code), u32::from(size));
+            },
+            _ if value == 0 => writer.write(0, QUANTIZE_BITS[resolution] as u32 - 1),
+            _ => writer.write(value.unsigned_abs() << 1 | u32::from(value < 0), QUANTIZE_BITS[resolution] as u32)
+        }
+    }
+
+    /// Cheapest width of the differences between scale factors (0 when all are zero, 6 when written as they are), and its size in bits
+    fn scale_coding(scales: &[u8]) -> (u32, u32) {
+        if scales.iter().all(|&scale| scale == 0) {
+            return (0, 3);
+        }
+        let mut best = (6, 3 + 6 * scales.len() as u32);
+        for width in 1..6 {
+            let half = ((1 << width) - 1) >> 1;
+            let size = 3 + 6 + scales.windows(2).map(|pair| {
+                let delta = i32::from(pair[1]) - i32::from(pair[0]) + half;
+                if (0..(1 << width) - 1).contains(&delta) { width } else { width + 6 }
+            }).sum::<u32>();
+            if size < best.1 {
+                best = (width, size);
+            }
+        }
+        best
+    }
+
+    fn write_scales(writer: &mut BitWriter, scales: &[u8]) {
+        let (width, _) = Self::scale_coding(scales);
+        writer.write(width, 3);
+        match width {
+            0 => {},
+            6 => scales.iter().for_each(|&scale| writer.write(u32::from(scale), 6)),
+            _ => {
+                let half = ((1 << width) - 1) >> 1;
+                writer.write(u32::from(scales[0]), 6);
+                for pair in scales.windows(2) {
+                    let delta = i32::from(pair[1]) - i32::from(pair[0]) + half;
+                    if (0..(1 << width) - 1).contains(&delta) {
+                        writer.write(delta as u32, width);
+                    } else {
+                        writer.write((1 << width) - 1, width);
+                        writer.write(u32::from(pair[1]), 6);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Size of a block in bits, at a noise level
+    fn block_size(&self, spectra: &[BlockSpectrum], noise: i32, ath_table: &[u8; 0x80], band_count: usize) -> u32 {
+        32 + spectra.iter().map(|spectrum| {
+            let scales = Self::scales_at(spectrum, noise, ath_table, band_count);
+            Self::scale_coding(&scales[..band_count]).1 + scales[..band_count].iter().enumerate().map(|(band, &scale)| {
+                let resolution = Self::resolution(scale, band, noise, ath_table);
+                spectrum.coefficients.iter()
+                    .map(|subframe| self.code_size(Self::quantize(subframe[band], scale, resolution), resolution))
+                    .sum::<u32>()
+            }).sum::<u32>()
+        }).sum::<u32>()
+    }
+
+    /// Encode a block at the lowest noise level its size allows
+    fn encode_block(&self, spectra: &[BlockSpectrum], ath_table: &[u8; 0x80], block_size: usize, band_count: usize) -> Vec<u8> {
+        // Noise levels are written as (9 bits << 8) - 7 bits; index them in increasing order.
+        // The decoder may read a few bits past the last code, which must fit in the block
+        let noise = |index: i32| ((index >> 7) << 8) - (0x7F - (index & 0x7F));
+        let budget = block_size as u32 * 8 - 16 - 4;
+        // Every band is left out at the highest level, which always fits
+        let mut lowest = 0;
+        let mut highest = 0xFFFF;
+        while lowest < highest {
+            let middle = i32::midpoint(lowest, highest);
+            if self.block_size(spectra, noise(middle), ath_table, band_count) <= budget {
+                highest = middle;
+            } else {
+                lowest = middle + 1;
+            }
+        }
+        let level = noise(highest);
+        let scales: Vec<[u8; 0x80]> = spectra.iter().map(|spectrum| Self::scales_at(spectrum, level, ath_table, band_count)).collect();
+
+        let mut writer = BitWriter { data: Vec::with_capacity(block_size), bit: 0 };
+        writer.write(0xFFFF, 16);
+        writer.write((highest >> 7) as u32, 9);
+        writer.write((0x7F - (highest & 0x7F)) as u32, 7);
+        for channel_scales in &scales {
+            Self::write_scales(&mut writer, &channel_scales[..band_count]);
+        }
+        for subframe in 0..8 {
+            for (spectrum, channel_scales) in spectra.iter().zip(&scales) {
+                for (band, &scale) in channel_scales[..band_count].iter().enumerate() {
+                    let resolution = Self::resolution(scale, band, level, ath_table);
+                    let value = Self::quantize(spectrum.coefficients[subframe][band], scale, resolution);
+                    self.write_code(&mut writer, value, resolution);
+                }
+            }
+        }
+
+        let mut data = writer.data;
+        data.resize(block_size - 2, 0);
+        for byte in &mut data {
+            *byte = self.cipher_table[usize::from(*byte)];
+        }
+        let checksum = HCAFile::checksum(&data, block_size - 2);
+        data.extend(checksum.to_be_bytes());
+        data
+    }
+}
+
+#[cfg(test)]
+mod hca_encoder_tests {
+    use super::*;
+
+    const RATE: u32 = 48000;
+
+    /// A 16-bit PCM WAV file of `frames` frames, a chord of sines on every channel
+    pub fn wav(channel_count: u16, frames: u32) -> (Vec<u8>, Vec<f64>) {
+        let samples: Vec<f64> = (0..frames).flat_map(|i| (0..channel_count).map(move |channel| {
+            let sine = |frequency: u32| (2.0 * std::f64::consts::PI * f64::from(frequency * i) / f64::from(RATE)).sin();
+            0.3_f64.mul_add(sine(440 + 110 * u32::from(channel)), 0.1 * sine(3000))
+        })).map(|sample| (sample * 32767.0).round() / 32767.0).collect();
+        let data_size = frames * u32::from(channel_count) * 2;
+        let mut wav: Vec<u8> = Vec::new();
+        wav.extend(b"RIFF");
+        wav.extend((36 + data_size).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16_u32.to_le_bytes());
+        wav.extend(1_u16.to_le_bytes());
+        wav.extend(channel_count.to_le_bytes());
+        wav.extend(RATE.to_le_bytes());
+        wav.extend((RATE * u32::from(channel_count) * 2).to_le_bytes());
+        wav.extend((channel_count * 2).to_le_bytes());
+        wav.extend(16_u16.to_le_bytes());
+        wav.extend(b"data");
+        wav.extend(data_size.to_le_bytes());
+        for sample in &samples {
+            wav.extend(((sample * 32767.0) as i16).to_le_bytes());
+        }
+        (wav, samples)
+    }
+
+    /// Encode a WAV file, decode it back as floats, and return the signal to noise ratio of the samples, in decibels
+    fn round_trip(options: HCAEncoderOptions, channel_count: u16, frames: u32) -> f64 {
+        let (input, samples) = wav(channel_count, frames);
+        let mut hca: Vec<u8> = Vec::new();
+        HCAEncoder::new(options).unwrap().encode(Cursor::new(input), &mut hca).unwrap();
+
+        let mut file = HCAFile::from_reader(&mut hca.as_slice(), options.key2.to_le_bytes(), options.key1.to_le_bytes()).unwrap();
+        assert_eq!(file.hca_header.encoder_delay, HCA_ENCODER_DELAY as u16);
+        file.set_pcm_options(PCMOptions { format: PCMFormat::Float, gain: 1.0 });
+        let mut output: Vec<u8> = Vec::new();
+        assert!(file.write_wav(&mut output).unwrap().is_empty());
+
+        let data = output.windows(4).position(|window| window == b"data").unwrap() + 8;
+        let decoded: Vec<f64> = output[data..].chunks_exact(4)
+            .map(|sample| f64::from(f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]])))
+            .collect();
+        // The decoded audio starts with the delay of the encoder
+        let delay = HCA_ENCODER_DELAY as usize * usize::from(channel_count);
+        assert!(decoded.len() >= delay + samples.len());
+        let (signal, noise) = samples.iter().zip(&decoded[delay..])
+            .fold((0.0, 0.0), |(signal, noise), (input, output)| (input.mul_add(*input, signal), (input - output).mul_add(input - output, noise)));
+        10.0 * (signal / noise).log10()
+    }
+
+    #[test]
+    fn stereo_round_trip() {
+        let snr = round_trip(HCAEncoderOptions::default(), 2, RATE / 2);
+        assert!(snr > 55.0, "SNR of {snr:.1} dB");
+    }
+
+    #[test]
+    fn mono_round_trip_at_lowest_quality() {
+        let snr = round_trip(HCAEncoderOptions { quality: HCAQuality::Lowest, ..Default::default() }, 1, 3000);
+        assert!(snr > 35.0, "SNR of {snr:.1} dB");
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let options = HCAEncoderOptions {
+            version: HCAVersion::V3_0,
+            cipher_type: Some(56),
+            key2: 0x0BCD_EF01,
+            key1: 0x00A1_2345,
+            ..Default::default()
+        };
+        let snr = round_trip(options, 2, RATE / 4);
+        assert!(snr > 55.0, "SNR of {snr:.1} dB");
+    }
+
+    #[test]
+    fn surround_round_trip() {
+        let snr = round_trip(HCAEncoderOptions::default(), 6, RATE / 4);
+        assert!(snr > 55.0, "SNR of {snr:.1} dB");
+    }
+}
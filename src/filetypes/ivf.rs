@@ -0,0 +1,424 @@
+/// Position and time of one frame of an IVF file
+#[derive(Clone, Copy, Debug)]
+struct IVFFrame {
+    offset: u64,
+    size: u32,
+    pts: u64
+}
+
+/// An IVF video file, as demuxed from USM files, with the index of its frames
+pub struct IVFFile<R: Read + Seek> {
+    fourcc: [u8; 4],
+    width: u16,
+    height: u16,
+    rate: u32,
+    scale: u32,
+    frames: Vec<IVFFrame>,
+    reader: R
+}
+
+impl IVFFile<BufReader<File>> {
+    /// Open and index the IVF file at `path`
+    pub fn open(path: &Path) -> GICSResult<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> IVFFile<R> {
+    /// Index an IVF stream read from any seekable source
+    pub fn from_reader(mut reader: R) -> GICSResult<Self> {
+        let mut header: [u8; 0x20] = [0; 0x20];
+        reader.read_exact(&mut header)?;
+        if &header[0..4] != b"DKIF" {
+            return Err(GICSError::new("Not an IVF file"));
+        }
+        let read16 = |offset: usize| u16::from_le_bytes([header[offset], header[offset + 1]]);
+        let read32 = |offset: usize| u32::from_le_bytes([header[offset], header[offset + 1], header[offset + 2], header[offset + 3]]);
+        let mut res = Self {
+            fourcc: [header[8], header[9], header[10], header[11]],
+            width: read16(0x0C),
+            height: read16(0x0E),
+            rate: read32(0x10),
+            scale: read32(0x14),
+            frames: Vec::new(),
+            reader
+        };
+
+        // Only read the frame headers, frames are read when asked for
+        let end = res.reader.seek(SeekFrom::End(0))?;
+        let mut offset = u64::from(read16(0x06));
+        let mut frame_header: [u8; 12] = [0; 12];
+        while offset + 12 <= end {
+            res.reader.seek(SeekFrom::Start(offset))?;
+            res.reader.read_exact(&mut frame_header)?;
+            let size = u32::from_le_bytes([frame_header[0], frame_header[1], frame_header[2], frame_header[3]]);
+            if offset + 12 + u64::from(size) > end {
+                return Err(GICSError::new("Truncated IVF frame"));
+            }
+            res.frames.push(IVFFrame {
+                offset: offset + 12,
+                size,
+                pts: u64::from_le_bytes(frame_header[4..12].try_into().unwrap_or_default())
+            });
+            offset += 12 + u64::from(size);
+        }
+        Ok(res)
+    }
+
+    /// `FourCC` of the codec of the stream (`VP90` for VP9)
+    #[must_use]
+    pub fn fourcc(&self) -> &str {
+        std::str::from_utf8(&self.fourcc).unwrap_or_default()
+    }
+
+    /// Width of the picture
+    #[must_use]
+    pub const fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Height of the picture
+    #[must_use]
+    pub const fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Number of frames in the file
+    #[must_use]
+    pub const fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Duration of one frame, in nanoseconds
+    #[must_use]
+    pub fn frame_duration(&self) -> Option<u64> {
+        (self.rate != 0).then(|| u64::from(self.scale) * 1_000_000_000 / u64::from(self.rate))
+    }
+
+    /// Time of the frame at `index`, in milliseconds
+    #[must_use]
+    pub fn frame_time(&self, index: usize) -> Option<u64> {
+        let frame = self.frames.get(index)?;
+        (self.rate != 0).then(|| frame.pts * u64::from(self.scale) * 1000 / u64::from(self.rate))
+    }
+
+    /// Duration of the whole stream, in milliseconds
+    #[must_use]
+    pub fn duration(&self) -> u64 {
+        let last = self.frames.len().checked_sub(1).and_then(|index| self.frame_time(index)).unwrap_or(0);
+        last + self.frame_duration().unwrap_or(0) / 1_000_000
+    }
+
+    /// Read the frame at `index`
+    pub fn read_frame(&mut self, index: usize) -> GICSResult<Vec<u8>> {
+        let frame = *self.frames.get(index).ok_or_else(|| GICSError::new("IVF frame index out of bounds"))?;
+        self.reader.seek(SeekFrom::Start(frame.offset))?;
+        let mut data = vec![0u8; frame.size as usize];
+        self.reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+}
+
+/// Whether a VP8 or VP9 frame is a key frame, from its uncompressed header
+fn is_key_frame(fourcc: &str, frame: &[u8]) -> bool {
+    let Some(&first) = frame.first() else { return false; };
+    match fourcc {
+        // The frame type is the lowest bit of a VP8 frame tag, 0 for key frames
+        "VP80" => first & 1 == 0,
+        "VP90" => {
+            // frame_marker (2 bits), profile (2 bits, a reserved bit for profile 3), then
+            // show_existing_frame and frame_type (0 for key frames)
+            let profile = (first >> 5 & 1) | (first >> 3 & 2);
+            let shift = if profile == 3 { 2 } else { 3 };
+            first >> 6 == 2 && first >> shift & 1 == 0 && first >> (shift - 1) & 1 == 0
+        },
+        _ => true
+    }
+}
+
+/// Reader of the big-endian bit fields of a VP9 uncompressed header
+struct VP9BitReader<'a> {
+    data: &'a [u8],
+    bit: usize
+}
+
+impl VP9BitReader<'_> {
+    fn read(&mut self, bits: usize) -> Option<u32> {
+        let mut value: u32 = 0;
+        for _ in 0..bits {
+            let byte = self.data.get(self.bit >> 3)?;
+            value = value << 1 | u32::from(byte >> (7 - (self.bit & 7)) & 1);
+            self.bit += 1;
+        }
+        Some(value)
+    }
+
+    fn skip_if_set(&mut self, bits: usize) -> Option<()> {
+        if self.read(1)? == 1 {
+            self.read(bits)?;
+        }
+        Some(())
+    }
+}
+
+/// Layout of a VP9 frame, as read from its uncompressed header
+#[derive(Clone, Copy, Debug)]
+struct VP9Layout {
+    width: u32,
+    /// Offset of the compressed header
+    compressed_header: usize,
+    /// Size of the compressed header
+    header_size: usize,
+    /// Number of tiles, whose data follows the compressed header
+    tile_count: usize
+}
+
+impl VP9BitReader<'_> {
+    fn color_config(&mut self, profile: u32) -> Option<()> {
+        if profile >= 2 {
+            self.read(1)?;
+        }
+        if self.read(3)? == 7 {
+            if profile & 1 == 1 {
+                self.read(1)?;
+            }
+        } else {
+            self.read(1)?;
+            if profile & 1 == 1 {
+                self.read(3)?;
+            }
+        }
+        Some(())
+    }
+}
+
+/// Read the layout of a VP9 frame from its uncompressed header.
+///
+/// Inter frames take their size from a reference frame, assumed to be `ref_width` wide. Returns `None` for frames
+/// without tiles or headers running past `frame`, and an error if the header cannot be valid
+#[allow(clippy::too_many_lines)]
+fn vp9_layout(frame: &[u8], ref_width: Option<u32>) -> Option<Result<VP9Layout, ()>> {
+    let mut reader = VP9BitReader { data: frame, bit: 0 };
+    if reader.read(2)? != 2 {
+        return Some(Err(()));
+    }
+    let profile = reader.read(1)? | reader.read(1)? << 1;
+    if profile == 3 {
+        reader.read(1)?;
+    }
+    // Shown existing frames only point at another frame
+    if reader.read(1)? == 1 {
+        return None;
+    }
+    let key_frame = reader.read(1)? == 0;
+    let show_frame = reader.read(1)? == 1;
+    let error_resilient = reader.read(1)? == 1;
+    let width = if key_frame {
+        if reader.read(24)? != 0x0049_8342 {
+            return Some(Err(()));
+        }
+        reader.color_config(profile)?;
+        let width = reader.read(16)? + 1;
+        reader.read(16)?;
+        reader.skip_if_set(32)?;
+        width
+    } else {
+        let intra_only = !show_frame && reader.read(1)? == 1;
+        if !error_resilient {
+            reader.read(2)?;
+        }
+        if intra_only {
+            if reader.read(24)? != 0x0049_8342 {
+                return Some(Err(()));
+            }
+            if profile > 0 {
+                reader.color_config(profile)?;
+            }
+            reader.read(8)?;
+            let width = reader.read(16)? + 1;
+            reader.read(16)?;
+            reader.skip_if_set(32)?;
+            width
+        } else {
+            reader.read(8)?;
+            reader.read(12)?;
+            let mut found_ref = false;
+            for _ in 0..3 {
+                if reader.read(1)? == 1 {
+                    found_ref = true;
+                    break;
+                }
+            }
+            let width = if found_ref {
+                ref_width?
+            } else {
+                let width = reader.read(16)? + 1;
+                reader.read(16)?;
+                width
+            };
+            reader.skip_if_set(32)?;
+            // High precision motion vectors, then the interpolation filter
+            reader.read(1)?;
+            if reader.read(1)? == 0 {
+                reader.read(2)?;
+            }
+            width
+        }
+    };
+    if !error_resilient {
+        reader.read(2)?;
+    }
+    reader.read(2)?;
+    // Loop filter
+    reader.read(9)?;
+    if reader.read(1)? == 1 && reader.read(1)? == 1 {
+        for _ in 0..6 {
+            reader.skip_if_set(7)?;
+        }
+    }
+    // Quantization
+    reader.read(8)?;
+    for _ in 0..3 {
+        reader.skip_if_set(5)?;
+    }
+    // Segmentation
+    if reader.read(1)? == 1 {
+        if reader.read(1)? == 1 {
+            for _ in 0..7 {
+                reader.skip_if_set(8)?;
+            }
+            if reader.read(1)? == 1 {
+                for _ in 0..3 {
+                    reader.skip_if_set(8)?;
+                }
+            }
+        }
+        if reader.read(1)? == 1 {
+            reader.read(1)?;
+            for _ in 0..8 {
+                for (bits, signed) in [(8, 1), (6, 1), (2, 0), (0, 0)] {
+                    reader.skip_if_set(bits + signed)?;
+                }
+            }
+        }
+    }
+    // Tiles
+    let sb64_cols = (width + 63) >> 6;
+    let mut min_log2 = 0;
+    while 64 << min_log2 < sb64_cols {
+        min_log2 += 1;
+    }
+    let mut max_log2 = 1;
+    while sb64_cols >> max_log2 >= 4 {
+        max_log2 += 1;
+    }
+    let mut cols_log2 = min_log2;
+    while cols_log2 < max_log2 - 1 && reader.read(1)? == 1 {
+        cols_log2 += 1;
+    }
+    let mut rows_log2 = reader.read(1)?;
+    if rows_log2 == 1 {
+        rows_log2 += reader.read(1)?;
+    }
+    let header_size = reader.read(16)? as usize;
+    if header_size == 0 {
+        return Some(Err(()));
+    }
+    Some(Ok(VP9Layout {
+        width,
+        compressed_header: reader.bit.div_ceil(8),
+        header_size,
+        tile_count: 1 << (cols_log2 + rows_log2)
+    }))
+}
+
+/// Check the structure of a VP9 frame beyond its first bytes, which USM encryption leaves clear.
+///
+/// The compressed header and every tile start with a bool decoder marker bit that must be zero, and the size
+/// of every tile but the last one must fit in the frame. Inter frames are assumed to be `ref_width` wide when they
+/// take the size of a reference frame. Returns `None` for frames which cannot be checked, and their layout
+/// otherwise, or an error if the frame cannot be valid
+fn check_vp9_frame(frame: &[u8], ref_width: Option<u32>) -> Option<Result<VP9Layout, ()>> {
+    let Ok(layout) = vp9_layout(frame, ref_width)? else {
+        return Some(Err(()));
+    };
+
+    // The compressed header, then every tile, starts with a clear marker bit
+    let mut offset = layout.compressed_header;
+    if frame.get(offset)? & 0x80 != 0 {
+        return Some(Err(()));
+    }
+    offset += layout.header_size;
+    for tile in 0..layout.tile_count {
+        if tile + 1 < layout.tile_count {
+            let size = u32::from_be_bytes(frame.get(offset..offset + 4)?.try_into().ok()?) as usize;
+            offset += 4;
+            if size == 0 || offset + size >= frame.len() {
+                return Some(Err(()));
+            }
+            if frame[offset] & 0x80 != 0 {
+                return Some(Err(()));
+            }
+            offset += size;
+        } else if frame.get(offset)? & 0x80 != 0 {
+            return Some(Err(()));
+        }
+    }
+    Some(Ok(layout))
+}
+
+#[cfg(test)]
+mod ivf_tests {
+    use super::*;
+
+    /// IVF stream of VP9 frames 320x180 large, at 30 frames per second
+    pub fn ivf(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut res: Vec<u8> = b"DKIF\0\0\x20\0VP90".to_vec();
+        for field in [320_u16, 180] {
+            res.extend(field.to_le_bytes());
+        }
+        for field in [30_u32, 1, frames.len() as u32, 0] {
+            res.extend(field.to_le_bytes());
+        }
+        for (pts, frame) in frames.iter().enumerate() {
+            res.extend((frame.len() as u32).to_le_bytes());
+            res.extend((pts as u64).to_le_bytes());
+            res.extend(frame);
+        }
+        res
+    }
+
+    /// VP9 frame of `size` bytes, a key frame or not
+    pub fn vp9_frame(key_frame: bool, size: usize) -> Vec<u8> {
+        let mut frame = vec![if key_frame { 0x82 } else { 0x86 }];
+        frame.resize(size, 0x11);
+        frame
+    }
+
+    #[test]
+    fn parse_header() {
+        let frames = [vp9_frame(true, 0x40), vp9_frame(false, 0x10), vp9_frame(false, 0x20)];
+        let mut file = IVFFile::from_reader(Cursor::new(ivf(&frames))).unwrap();
+        assert_eq!(file.fourcc(), "VP90");
+        assert_eq!((file.width(), file.height()), (320, 180));
+        assert_eq!(file.frame_count(), 3);
+        assert_eq!(file.frame_duration(), Some(33_333_333));
+        assert_eq!(file.frame_time(2), Some(66));
+        assert_eq!(file.frame_time(3), None);
+        assert_eq!(file.duration(), 99);
+        assert_eq!(file.read_frame(1).unwrap(), frames[1]);
+        assert!(file.read_frame(3).is_err());
+        assert!(is_key_frame(file.fourcc(), &frames[0]));
+        assert!(!is_key_frame(file.fourcc(), &frames[1]));
+    }
+
+    #[test]
+    fn invalid_files() {
+        let data = ivf(&[vp9_frame(true, 0x40)]);
+        assert!(IVFFile::from_reader(Cursor::new(&data[..0x1F])).is_err());
+        assert!(IVFFile::from_reader(Cursor::new(&data[..data.len() - 1])).is_err());
+        let mut wrong = data;
+        wrong[0] = b'R';
+        assert!(IVFFile::from_reader(Cursor::new(wrong)).is_err());
+    }
+}
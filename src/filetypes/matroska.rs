@@ -0,0 +1,500 @@
+// EBML and Matroska element identifiers
+const EBML_HEADER: u32 = 0x1A45_DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const EBML_DOC_TYPE: u32 = 0x4282;
+const EBML_DOC_TYPE_VERSION: u32 = 0x4287;
+const EBML_DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const EBML_VOID: u32 = 0xEC;
+const MKV_SEGMENT: u32 = 0x1853_8067;
+const MKV_SEEK_HEAD: u32 = 0x114D_9B74;
+const MKV_SEEK: u32 = 0x4DBB;
+const MKV_SEEK_ID: u32 = 0x53AB;
+const MKV_SEEK_POSITION: u32 = 0x53AC;
+const MKV_INFO: u32 = 0x1549_A966;
+const MKV_TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+const MKV_DURATION: u32 = 0x4489;
+const MKV_MUXING_APP: u32 = 0x4D80;
+const MKV_WRITING_APP: u32 = 0x5741;
+const MKV_TRACKS: u32 = 0x1654_AE6B;
+const MKV_TRACK_ENTRY: u32 = 0xAE;
+const MKV_TRACK_NUMBER: u32 = 0xD7;
+const MKV_TRACK_UID: u32 = 0x73C5;
+const MKV_TRACK_TYPE: u32 = 0x83;
+const MKV_FLAG_DEFAULT: u32 = 0x88;
+const MKV_FLAG_LACING: u32 = 0x9C;
+const MKV_DEFAULT_DURATION: u32 = 0x23_E383;
+const MKV_NAME: u32 = 0x536E;
+const MKV_LANGUAGE: u32 = 0x22_B59C;
+const MKV_CODEC_ID: u32 = 0x86;
+const MKV_CODEC_PRIVATE: u32 = 0x63A2;
+const MKV_VIDEO: u32 = 0xE0;
+const MKV_PIXEL_WIDTH: u32 = 0xB0;
+const MKV_PIXEL_HEIGHT: u32 = 0xBA;
+const MKV_AUDIO: u32 = 0xE1;
+const MKV_SAMPLING_FREQUENCY: u32 = 0xB5;
+const MKV_CHANNELS: u32 = 0x9F;
+const MKV_BIT_DEPTH: u32 = 0x6264;
+const MKV_CLUSTER: u32 = 0x1F43_B675;
+const MKV_TIMESTAMP: u32 = 0xE7;
+const MKV_SIMPLE_BLOCK: u32 = 0xA3;
+const MKV_BLOCK_GROUP: u32 = 0xA0;
+const MKV_BLOCK: u32 = 0xA1;
+const MKV_BLOCK_DURATION: u32 = 0x9B;
+const MKV_CUES: u32 = 0x1C53_BB6B;
+const MKV_CUE_POINT: u32 = 0xBB;
+const MKV_CUE_TIME: u32 = 0xB3;
+const MKV_CUE_TRACK_POSITIONS: u32 = 0xB7;
+const MKV_CUE_TRACK: u32 = 0xF7;
+const MKV_CUE_CLUSTER_POSITION: u32 = 0xF1;
+
+/// Room kept at the start of the segment for the seek head, written once everything else is
+const SEEK_HEAD_SPACE: usize = 0x60;
+
+/// In-memory EBML element builder
+#[derive(Default)]
+struct EBMLBuffer {
+    data: Vec<u8>
+}
+
+impl EBMLBuffer {
+    fn id(&mut self, id: u32) {
+        let bytes = id.to_be_bytes();
+        let skip = bytes.iter().position(|&b| b != 0).unwrap_or(3);
+        self.data.extend_from_slice(&bytes[skip..]);
+    }
+
+    /// Write a size as the shortest possible variable size integer
+    fn size(&mut self, size: u64) {
+        let length = (1..8).find(|&length| size < (1 << (7 * length)) - 1).unwrap_or(8);
+        let marked = size | 1 << (7 * length);
+        self.data.extend_from_slice(&marked.to_be_bytes()[8 - length as usize..]);
+    }
+
+    fn binary(&mut self, id: u32, value: &[u8]) -> &mut Self {
+        self.id(id);
+        self.size(value.len() as u64);
+        self.data.extend_from_slice(value);
+        self
+    }
+
+    fn uint(&mut self, id: u32, value: u64) -> &mut Self {
+        let bytes = value.to_be_bytes();
+        let skip = bytes.iter().position(|&b| b != 0).unwrap_or(7);
+        self.binary(id, &bytes[skip..])
+    }
+
+    fn float(&mut self, id: u32, value: f64) -> &mut Self {
+        self.binary(id, &value.to_be_bytes())
+    }
+
+    fn string(&mut self, id: u32, value: &str) -> &mut Self {
+        self.binary(id, value.as_bytes())
+    }
+
+    fn master<F: FnOnce(&mut Self)>(&mut self, id: u32, content: F) -> &mut Self {
+        let mut inner = Self::default();
+        content(&mut inner);
+        self.binary(id, &inner.data)
+    }
+
+    /// Fill exactly `size` bytes (at least 2) with a void element
+    fn void(&mut self, size: usize) -> &mut Self {
+        self.id(EBML_VOID);
+        // Sizes above 126 need a second byte
+        let content = if size - 2 > 0x7E {
+            self.data.extend_from_slice(&(0x4000 | (size - 3) as u16).to_be_bytes());
+            size - 3
+        } else {
+            self.size(size as u64 - 2);
+            size - 2
+        };
+        self.data.resize(self.data.len() + content, 0);
+        self
+    }
+}
+
+/// One block of a track, ready to be put in a cluster
+struct MKVPacket {
+    track: u64,
+    key_frame: bool,
+    duration: Option<u64>,
+    data: Vec<u8>
+}
+
+/// Where the blocks of a track come from
+enum MKVSource {
+    Video { file: IVFFile<BufReader<File>>, next: usize },
+    Audio { file: WaveReader<BufReader<File>>, next: u64 },
+    Subtitle { codec: &'static str, private: Vec<u8>, events: Vec<(u64, u64, Vec<u8>)>, next: usize }
+}
+
+impl MKVSource {
+    /// Time of the next block, if any is left
+    fn peek(&self) -> Option<u64> {
+        match self {
+            Self::Video { file, next } => file.frame_time(*next),
+            Self::Audio { file, next } => (*next * file.frame_size() < file.data_size)
+                .then(|| *next * 1000 / u64::from(file.sampling_rate.max(1))),
+            Self::Subtitle { events, next, .. } => events.get(*next).map(|e| e.0)
+        }
+    }
+
+    fn next_packet(&mut self, track: u64) -> GICSResult<MKVPacket> {
+        let (key_frame, duration, data) = match self {
+            Self::Video { file, next } => {
+                let data = file.read_frame(*next)?;
+                *next += 1;
+                (is_key_frame(file.fourcc(), &data), None, data)
+            },
+            Self::Audio { file, next } => {
+                // Blocks of a tenth of a second
+                let frames = u64::from(file.sampling_rate / 10).max(1);
+                let data = file.read_frames(*next, frames)?;
+                *next += frames;
+                (true, None, data)
+            },
+            Self::Subtitle { events, next, .. } => {
+                let (start, end, data) = std::mem::take(&mut events[*next]);
+                *next += 1;
+                (true, Some(end.saturating_sub(start)), data)
+            }
+        };
+        Ok(MKVPacket { track, key_frame, duration, data })
+    }
+}
+
+/// Subtitles read for muxing, from a SRT or ASS file
+fn subtitle_source(path: &Path) -> GICSResult<MKVSource> {
+    let reader = BufReader::new(File::open(path)?);
+    if path.extension().and_then(std::ffi::OsStr::to_str) != Some("ass") {
+        let track = SubtitleTrack::read_srt(reader, 0)?;
+        let events = track.entries.into_iter().map(|e| (e.start, e.end, e.text.into_bytes())).collect();
+        return Ok(MKVSource::Subtitle { codec: "S_TEXT/UTF8", private: Vec::new(), events, next: 0 });
+    }
+    // The script header goes in the codec private data, and every dialogue line becomes a block
+    // made of "ReadOrder, Layer, Style, Name, MarginL, MarginR, MarginV, Effect, Text"
+    let mut header = String::new();
+    let mut events: Vec<(u64, u64, Vec<u8>)> = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim_start_matches('\u{FEFF}');
+        let Some(dialogue) = line.strip_prefix("Dialogue:") else {
+            if events.is_empty() {
+                header.push_str(line);
+                header.push('\n');
+            }
+            continue;
+        };
+        let fields: Vec<&str> = dialogue.trim_start().splitn(10, ',').collect();
+        if fields.len() < 10 {
+            return Err(GICSError::new(&format!("Invalid ASS dialogue line \"{line}\"")));
+        }
+        let start = SubtitleEntry::parse_ass_time(fields[1]).ok_or_else(|| GICSError::new("Invalid ASS start time"))?;
+        let end = SubtitleEntry::parse_ass_time(fields[2]).ok_or_else(|| GICSError::new("Invalid ASS end time"))?;
+        let block = format!("{},{},{}", events.len(), fields[0], fields[3..].join(","));
+        events.push((start, end, block.into_bytes()));
+    }
+    events.sort_by_key(|e| e.0);
+    Ok(MKVSource::Subtitle { codec: "S_TEXT/ASS", private: header.trim_end().as_bytes().to_vec(), events, next: 0 })
+}
+
+/// Native Matroska writer, muxing IVF video, WAV audio and SRT/ASS subtitles
+struct MKVWriter<W: Write + Seek> {
+    output: W,
+    segment_start: u64
+}
+
+impl<W: Write + Seek> MKVWriter<W> {
+    fn position(&mut self) -> GICSResult<u64> {
+        Ok(self.output.stream_position()? - self.segment_start)
+    }
+
+    #[allow(clippy::too_many_lines, clippy::cast_precision_loss)]
+    fn write(mut self, tracks: Vec<(MKVSource, &str, String)>) -> GICSResult<()> {
+        let mut sources: Vec<MKVSource> = Vec::new();
+        let mut entries = EBMLBuffer::default();
+        let mut duration: u64 = 0;
+        let mut has_default: [bool; 3] = [false; 3];
+        for (number, (source, language, name)) in tracks.into_iter().enumerate() {
+            let number = number as u64 + 1;
+            entries.master(MKV_TRACK_ENTRY, |entry| {
+                entry.uint(MKV_TRACK_NUMBER, number)
+                    .uint(MKV_TRACK_UID, number)
+                    .uint(MKV_FLAG_LACING, 0)
+                    .string(MKV_LANGUAGE, language)
+                    .string(MKV_NAME, &name);
+                let kind = match &source {
+                    MKVSource::Video { file, .. } => {
+                        duration = duration.max(file.duration());
+                        let codec = match file.fourcc() {
+                            "VP80" => "V_VP8",
+                            "AV01" => "V_AV1",
+                            _ => "V_VP9"
+                        };
+                        entry.uint(MKV_TRACK_TYPE, 1)
+                            .string(MKV_CODEC_ID, codec)
+                            .master(MKV_VIDEO, |video| {
+                                video.uint(MKV_PIXEL_WIDTH, u64::from(file.width()))
+                                    .uint(MKV_PIXEL_HEIGHT, u64::from(file.height()));
+                            });
+                        if let Some(frame_duration) = file.frame_duration() {
+                            entry.uint(MKV_DEFAULT_DURATION, frame_duration);
+                        }
+                        0
+                    },
+                    MKVSource::Audio { file, .. } => {
+                        duration = duration.max(file.duration());
+                        let codec = if file.fmt_type == 3 { "A_PCM/FLOAT/IEEE" } else { "A_PCM/INT/LIT" };
+                        entry.uint(MKV_TRACK_TYPE, 2)
+                            .string(MKV_CODEC_ID, codec)
+                            .master(MKV_AUDIO, |audio| {
+                                audio.float(MKV_SAMPLING_FREQUENCY, f64::from(file.sampling_rate))
+                                    .uint(MKV_CHANNELS, u64::from(file.channel_count))
+                                    .uint(MKV_BIT_DEPTH, u64::from(file.bit_count));
+                            });
+                        1
+                    },
+                    MKVSource::Subtitle { codec, private, events, .. } => {
+                        duration = duration.max(events.iter().map(|e| e.1).max().unwrap_or(0));
+                        entry.uint(MKV_TRACK_TYPE, 0x11).string(MKV_CODEC_ID, codec);
+                        if !private.is_empty() {
+                            entry.binary(MKV_CODEC_PRIVATE, private);
+                        }
+                        2
+                    }
+                };
+                // The first track of every kind is the default one
+                entry.uint(MKV_FLAG_DEFAULT, u64::from(!has_default[kind]));
+                has_default[kind] = true;
+            });
+            sources.push(source);
+        }
+
+        // Headers
+        let mut header = EBMLBuffer::default();
+        header.master(EBML_HEADER, |ebml| {
+            ebml.uint(EBML_VERSION, 1)
+                .uint(EBML_READ_VERSION, 1)
+                .uint(EBML_MAX_ID_LENGTH, 4)
+                .uint(EBML_MAX_SIZE_LENGTH, 8)
+                .string(EBML_DOC_TYPE, "matroska")
+                .uint(EBML_DOC_TYPE_VERSION, 4)
+                .uint(EBML_DOC_TYPE_READ_VERSION, 2);
+        });
+        // The size of the segment is only known at the end, keep 8 bytes for it
+        header.id(MKV_SEGMENT);
+        header.data.extend_from_slice(&[0x01, 0, 0, 0, 0, 0, 0, 0]);
+        self.output.write_all(&header.data)?;
+        self.segment_start = self.output.stream_position()?;
+        self.output.write_all(&EBMLBuffer::default().void(SEEK_HEAD_SPACE).data)?;
+
+        let info_position = self.position()?;
+        let mut info = EBMLBuffer::default();
+        info.master(MKV_INFO, |info| {
+            info.uint(MKV_TIMESTAMP_SCALE, 1_000_000)
+                .float(MKV_DURATION, duration as f64)
+                .string(MKV_MUXING_APP, concat!("gi-cutscenes-rs ", env!("CARGO_PKG_VERSION")))
+                .string(MKV_WRITING_APP, concat!("gi-cutscenes-rs ", env!("CARGO_PKG_VERSION")));
+        });
+        let tracks_position = info_position + info.data.len() as u64;
+        info.binary(MKV_TRACKS, &entries.data);
+        self.output.write_all(&info.data)?;
+
+        // Interleave the blocks of every track by time, in clusters starting on key frames of the first track
+        let mut cues = EBMLBuffer::default();
+        let mut cluster = EBMLBuffer::default();
+        let mut cluster_time: Option<u64> = None;
+        loop {
+            let next = sources.iter().enumerate()
+                .filter_map(|(index, source)| source.peek().map(|time| (time, index)))
+                .min();
+            let Some((time, index)) = next else { break; };
+            let packet = sources[index].next_packet(index as u64 + 1)?;
+            let starts_cluster = cluster_time.is_none_or(|start| {
+                let age = time.saturating_sub(start);
+                age > i16::MAX as u64 || (packet.track == 1 && packet.key_frame && age >= 1000)
+            });
+            if starts_cluster {
+                self.flush_cluster(&mut cluster)?;
+                if packet.track == 1 && packet.key_frame {
+                    let position = self.position()?;
+                    cues.master(MKV_CUE_POINT, |point| {
+                        point.uint(MKV_CUE_TIME, time)
+                            .master(MKV_CUE_TRACK_POSITIONS, |positions| {
+                                positions.uint(MKV_CUE_TRACK, 1)
+                                    .uint(MKV_CUE_CLUSTER_POSITION, position);
+                            });
+                    });
+                }
+                cluster.uint(MKV_TIMESTAMP, time);
+                cluster_time = Some(time);
+            }
+
+            let mut block: Vec<u8> = Vec::with_capacity(packet.data.len() + 4);
+            block.push(0x80 | packet.track as u8);
+            block.extend_from_slice(&((time - cluster_time.unwrap_or(time)) as i16).to_be_bytes());
+            if let Some(duration) = packet.duration {
+                block.push(0);
+                block.extend_from_slice(&packet.data);
+                cluster.master(MKV_BLOCK_GROUP, |group| {
+                    group.binary(MKV_BLOCK, &block).uint(MKV_BLOCK_DURATION, duration);
+                });
+            } else {
+                block.push(if packet.key_frame { 0x80 } else { 0 });
+                block.extend_from_slice(&packet.data);
+                cluster.binary(MKV_SIMPLE_BLOCK, &block);
+            }
+        }
+        self.flush_cluster(&mut cluster)?;
+
+        let cues_position = self.position()?;
+        self.output.write_all(&EBMLBuffer::default().binary(MKV_CUES, &cues.data).data)?;
+        let segment_size = self.position()?;
+
+        // Now that everything is known, fill the seek head and the segment size
+        let mut seek_head = EBMLBuffer::default();
+        seek_head.master(MKV_SEEK_HEAD, |head| {
+            for (id, position) in [(MKV_INFO, info_position), (MKV_TRACKS, tracks_position), (MKV_CUES, cues_position)] {
+                head.master(MKV_SEEK, |seek| {
+                    seek.binary(MKV_SEEK_ID, &id.to_be_bytes())
+                        .binary(MKV_SEEK_POSITION, &position.to_be_bytes());
+                });
+            }
+        });
+        let space = SEEK_HEAD_SPACE - seek_head.data.len();
+        seek_head.void(space);
+        self.output.seek(SeekFrom::Start(self.segment_start - 8))?;
+        self.output.write_all(&(segment_size | 1 << 56).to_be_bytes())?;
+        self.output.write_all(&seek_head.data)?;
+        self.output.flush()?;
+        Ok(())
+    }
+
+    fn flush_cluster(&mut self, cluster: &mut EBMLBuffer) -> GICSResult<()> {
+        if !cluster.data.is_empty() {
+            let mut element = EBMLBuffer::default();
+            element.binary(MKV_CLUSTER, &cluster.data);
+            self.output.write_all(&element.data)?;
+            cluster.data.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod matroska_tests {
+    use super::*;
+    use super::ivf_tests::{ivf, vp9_frame};
+
+    /// An element read back : its ID, offset, and the range of its content
+    #[derive(Clone, Copy, Debug)]
+    struct Element {
+        id: u32,
+        offset: usize,
+        content: usize,
+        end: usize
+    }
+
+    fn vint(data: &[u8], offset: usize) -> (u64, usize) {
+        let length = data[offset].leading_zeros() as usize + 1;
+        let value = data[offset..offset + length].iter().fold(0, |value, &byte| value << 8 | u64::from(byte));
+        (value, length)
+    }
+
+    fn element(data: &[u8], offset: usize) -> Element {
+        let (id, id_length) = vint(data, offset);
+        let (size, size_length) = vint(data, offset + id_length);
+        let content = offset + id_length + size_length;
+        Element { id: id as u32, offset, content, end: content + (size & !(1 << (7 * size_length))) as usize }
+    }
+
+    /// Every element between `start` and `end`
+    fn elements(data: &[u8], start: usize, end: usize) -> Vec<Element> {
+        let mut res: Vec<Element> = Vec::new();
+        let mut offset = start;
+        while offset < end {
+            res.push(element(data, offset));
+            offset = res[res.len() - 1].end;
+        }
+        res
+    }
+
+    fn children(data: &[u8], parent: Element) -> Vec<Element> {
+        elements(data, parent.content, parent.end)
+    }
+
+    fn uint(data: &[u8], element: Element) -> u64 {
+        data[element.content..element.end].iter().fold(0, |value, &byte| value << 8 | u64::from(byte))
+    }
+
+    #[test]
+    fn variable_size_integers() {
+        let size = |size: u64| {
+            let mut buffer = EBMLBuffer::default();
+            buffer.size(size);
+            buffer.data
+        };
+        assert_eq!(size(0), [0x80]);
+        assert_eq!(size(0x7E), [0xFE]);
+        // All ones are reserved for unknown sizes
+        assert_eq!(size(0x7F), [0x40, 0x7F]);
+        assert_eq!(size(0x3FFE), [0x7F, 0xFE]);
+        assert_eq!(size(0x3FFF), [0x20, 0x3F, 0xFF]);
+        assert_eq!(size(1 << 48), [0x03, 0, 0, 0, 0, 0, 0]);
+
+        let mut buffer = EBMLBuffer::default();
+        buffer.uint(MKV_TRACK_NUMBER, 0).uint(MKV_TIMESTAMP_SCALE, 1_000_000).string(MKV_CODEC_ID, "V_VP9");
+        assert_eq!(buffer.data, [0xD7, 0x81, 0x00, 0x2A, 0xD7, 0xB1, 0x83, 0x0F, 0x42, 0x40, 0x86, 0x85, b'V', b'_', b'V', b'P', b'9']);
+        for size in [2, 0x80, 0x81, 0x82, SEEK_HEAD_SPACE] {
+            let mut buffer = EBMLBuffer::default();
+            buffer.void(size);
+            let void = element(&buffer.data, 0);
+            assert_eq!((void.id, void.end), (EBML_VOID, size));
+        }
+    }
+
+    #[test]
+    fn seek_head_and_cues() {
+        let directory = std::env::temp_dir().join(format!("gics_mkv_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let (video, output) = (directory.join("video.ivf"), directory.join("merged.mkv"));
+        // Key frames every second, for clusters and cue points to start there
+        let frames: Vec<Vec<u8>> = (0..90).map(|index| vp9_frame(index % 30 == 0, 0x20)).collect();
+        std::fs::write(&video, ivf(&frames)).unwrap();
+        MKVFile::mux(&output, &[video.as_path()], &[], &[]).unwrap();
+        let data = std::fs::read(&output).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let [ebml, segment] = elements(&data, 0, data.len())[..] else { panic!("not an EBML header and a segment") };
+        assert_eq!((ebml.id, segment.id), (EBML_HEADER, MKV_SEGMENT));
+        let top_level = children(&data, segment);
+        assert_eq!(top_level.iter().map(|element| element.id).filter(|&id| id != MKV_CLUSTER).collect::<Vec<u32>>(),
+            [MKV_SEEK_HEAD, EBML_VOID, MKV_INFO, MKV_TRACKS, MKV_CUES]);
+
+        // Positions are relative to the start of the content of the segment
+        let seeks = children(&data, top_level[0]);
+        assert_eq!(seeks.len(), 3);
+        for seek in seeks {
+            let [id, position] = children(&data, seek)[..] else { panic!("seek entry without 2 children") };
+            let target = u32::from_be_bytes(data[id.content..id.end].try_into().unwrap());
+            let position = segment.content + uint(&data, position) as usize;
+            assert!(top_level.iter().any(|element| element.id == target && element.offset == position), "{target:#X}");
+        }
+
+        let cues = top_level.iter().find(|element| element.id == MKV_CUES).unwrap();
+        let points = children(&data, *cues);
+        assert_eq!(points.len(), 3);
+        for (point, time) in points.into_iter().zip([0, 1000, 2000]) {
+            let [cue_time, positions] = children(&data, point)[..] else { panic!("cue point without 2 children") };
+            assert_eq!(uint(&data, cue_time), time);
+            let [track, position] = children(&data, positions)[..] else { panic!("cue positions without 2 children") };
+            assert_eq!(uint(&data, track), 1);
+            let cluster = element(&data, segment.content + uint(&data, position) as usize);
+            assert_eq!(cluster.id, MKV_CLUSTER);
+            assert_eq!(uint(&data, children(&data, cluster)[0]), time);
+        }
+    }
+}
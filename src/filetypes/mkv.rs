@@ -1,9 +1,21 @@
+/// Merge of video, audio and subtitle tracks into a MKV container, done with `ffmpeg` or natively
 pub struct MKVFile {
     outpath: PathBuf,
     merge_binary: PathBuf,
     command: Command
 }
 
+/// An audio or subtitle file to merge, with the language and title of its track
+#[derive(Clone, Copy, Debug)]
+pub struct MergeTrack<'a> {
+    /// Path of the file
+    pub path: &'a Path,
+    /// ISO 639-2 code of the language of the track (`und` when unknown)
+    pub language: &'a str,
+    /// Title of the track
+    pub title: &'a str
+}
+
 const GENSHIN_LANGUAGE_ORDER: [(&str, &str); 4] = [
     // Chinese is track 0
     ("chi", "Chinese (汉语)"),
@@ -15,9 +27,86 @@ const GENSHIN_LANGUAGE_ORDER: [(&str, &str); 4] = [
     ("kor", "Korean (한국어)")
 ];
 
+/// One channel of a language configuration file
+#[derive(Deserialize)]
+struct AudioLanguageEntry {
+    language: String,
+    #[serde(default)]
+    title: String
+}
+
+/// Language configuration file
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AudioLanguageFile {
+    audio_languages: HashMap<String, AudioLanguageEntry>
+}
+
+/// Language and title of the audio tracks, by audio channel number (`chno`).
+///
+/// Defaults to the channel order of Genshin's cutscenes : Chinese, English, Japanese and Korean
+#[derive(Clone, Debug)]
+pub struct AudioLanguages {
+    channels: BTreeMap<u8, (String, String)>
+}
+
+impl Default for AudioLanguages {
+    fn default() -> Self {
+        let mut res = Self { channels: BTreeMap::new() };
+        for (chno, (language, title)) in GENSHIN_LANGUAGE_ORDER.iter().enumerate() {
+            res.channels.insert(chno as u8, ((*language).into(), (*title).into()));
+        }
+        res
+    }
+}
+
+impl AudioLanguages {
+    /// Label the channel `chno` with an ISO 639-2 language code, and a title (the file name is used if empty)
+    pub fn set(&mut self, chno: u8, language: &str, title: &str) -> GICSResult<()> {
+        if language.len() != 3 || !language.bytes().all(|b| b.is_ascii_lowercase()) {
+            return Err(GICSError::new(&format!("\"{language}\" is not an ISO 639-2 language code")));
+        }
+        self.channels.insert(chno, (language.into(), title.into()));
+        Ok(())
+    }
+
+    /// Language code and title of the channel `chno`, if known
+    #[must_use]
+    pub fn get(&self, chno: u8) -> Option<(&str, &str)> {
+        self.channels.get(&chno).map(|(language, title)| (language.as_str(), title.as_str()))
+    }
+
+    /// Label a channel from a `chno=language[:title]` specification, such as `4=fre:Français`
+    pub fn set_from_spec(&mut self, spec: &str) -> GICSResult<()> {
+        let (chno, label) = spec.split_once('=')
+            .ok_or_else(|| GICSError::new(&format!("\"{spec}\" is not of the form chno=language[:title]")))?;
+        let chno: u8 = chno.trim().parse()
+            .map_err(|_| GICSError::new(&format!("\"{chno}\" is not an audio channel number")))?;
+        let (language, title) = label.split_once(':').unwrap_or((label, ""));
+        self.set(chno, language.trim(), title.trim())
+    }
+
+    /// Label channels from a JSON configuration file, such as
+    /// `{ "audioLanguages": { "4": { "language": "fre", "title": "Français" } } }`.
+    ///
+    /// Channels the file does not mention keep their current label
+    pub fn read_file(&mut self, path: &Path) -> GICSResult<()> {
+        let content = std::fs::read_to_string(path)?;
+        let parsed: AudioLanguageFile = serde_json::from_str(&content)
+            .map_err(|e| GICSError::new(&format!("Invalid language configuration file : {e}")))?;
+        for (chno, entry) in parsed.audio_languages {
+            let chno: u8 = chno.trim().parse()
+                .map_err(|_| GICSError::new(&format!("\"{chno}\" is not an audio channel number")))?;
+            self.set(chno, &entry.language, &entry.title)?;
+        }
+        Ok(())
+    }
+}
+
 impl MKVFile {
-    pub fn attempt_merge(out_path: PathBuf, v_path: &Path, a_paths: &[&Path], ffmpeg_path: &str) -> GICSResult<()> {
-        let mut russian_doll = Self::new(out_path, v_path, a_paths, ffmpeg_path);
+    /// Merge the videos at `v_paths` with the audio tracks `a_tracks` and the subtitles `s_tracks` into `out_path`
+    pub fn attempt_merge(out_path: PathBuf, v_paths: &[&Path], a_tracks: &[MergeTrack], s_tracks: &[MergeTrack], ffmpeg_path: &str) -> GICSResult<()> {
+        let mut russian_doll = Self::new(out_path, v_paths, a_tracks, s_tracks, ffmpeg_path);
         let status = russian_doll.command.status()?;
         if status.success() {
             Ok(())
@@ -26,27 +115,62 @@ impl MKVFile {
         }
     }
 
-    fn new(out_path: PathBuf, v_path: &Path, a_paths: &[&Path], ffmpeg_path: &str) -> Self {
+    /// Merge the videos at `v_paths` with the audio tracks `a_tracks` and the subtitles `s_tracks` into `out_path`,
+    /// without any external program.
+    ///
+    /// Videos are IVF files, audio tracks PCM WAV files (kept as PCM), and subtitles SRT or ASS files
+    pub fn mux(out_path: &Path, v_paths: &[&Path], a_tracks: &[MergeTrack], s_tracks: &[MergeTrack]) -> GICSResult<()> {
+        let name_of = |path: &Path| path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut tracks: Vec<(MKVSource, &str, String)> = Vec::new();
+        for path in v_paths {
+            tracks.push((MKVSource::Video { file: IVFFile::open(path)?, next: 0 }, "und", name_of(path)));
+        }
+        for track in a_tracks {
+            let file = WaveReader::new(BufReader::new(File::open(track.path)?))?;
+            tracks.push((MKVSource::Audio { file, next: 0 }, track.language, track.title.into()));
+        }
+        for track in s_tracks {
+            tracks.push((subtitle_source(track.path)?, track.language, track.title.into()));
+        }
+        MKVWriter { output: BufWriter::new(File::create(out_path)?), segment_start: 0 }.write(tracks)
+    }
+
+    fn new(out_path: PathBuf, v_paths: &[&Path], a_tracks: &[MergeTrack], s_tracks: &[MergeTrack], ffmpeg_path: &str) -> Self {
         // Build an argument vector
-        let mut input_arguments: Vec<String> = vec!["-i".into(), v_path.to_str().unwrap().into()];
-        let mut map_arguments: Vec<String> = vec!["-map".into(), "0:v".into()];
+        let mut input_arguments: Vec<String> = Vec::new();
+        let mut map_arguments: Vec<String> = Vec::new();
+        for (num, video_path) in v_paths.iter().enumerate() {
+            input_arguments.push("-i".into());
+            input_arguments.push(video_path.to_str().unwrap().into());
+            map_arguments.push("-map".into());
+            map_arguments.push(format!("{num}:v"));
+        }
         let mut metadata_arguments: Vec<String> = Vec::new();
-        let merge_arguments: Vec<String> = vec!["-c:v".into(), "copy".into(), "-c:a".into(), "libopus".into()];
-        for (num, audio_path) in a_paths.iter().enumerate() {
+        let merge_arguments: Vec<String> = vec!["-c:v".into(), "copy".into(), "-c:a".into(), "libopus".into(), "-c:s".into(), "copy".into()];
+        for (num, audio_track) in a_tracks.iter().enumerate() {
             // Add an input argument
             input_arguments.push("-i".into());
-            input_arguments.push(audio_path.to_str().unwrap().into());
+            input_arguments.push(audio_track.path.to_str().unwrap().into());
             // Add the mapping of the audio
             map_arguments.push("-map".into());
-            map_arguments.push(format!("{}:a", num+1));
+            map_arguments.push(format!("{}:a", v_paths.len() + num));
             // Add the metadata
-            let (lang_hint, lang_desc) = GENSHIN_LANGUAGE_ORDER[num];
             // First the language hint
-            metadata_arguments.push(format!("-metadata:s:a:{}", num));
-            metadata_arguments.push(format!("language={}", lang_hint));
+            metadata_arguments.push(format!("-metadata:s:a:{num}"));
+            metadata_arguments.push(format!("language={}", audio_track.language));
             // Second the language description
-            metadata_arguments.push(format!("-metadata:s:a:{}", num));
-            metadata_arguments.push(format!("title=\"{}\"", lang_desc));
+            metadata_arguments.push(format!("-metadata:s:a:{num}"));
+            metadata_arguments.push(format!("title={}", audio_track.title));
+        }
+        for (num, sub_track) in s_tracks.iter().enumerate() {
+            input_arguments.push("-i".into());
+            input_arguments.push(sub_track.path.to_str().unwrap().into());
+            map_arguments.push("-map".into());
+            map_arguments.push(format!("{}:s", v_paths.len() + a_tracks.len() + num));
+            metadata_arguments.push(format!("-metadata:s:s:{num}"));
+            metadata_arguments.push(format!("language={}", sub_track.language));
+            metadata_arguments.push(format!("-metadata:s:s:{num}"));
+            metadata_arguments.push(format!("title={}", sub_track.title));
         }
         // Check ffmpeg_path
         let mut cmd: Command = Command::new(ffmpeg_path);
@@ -69,10 +193,20 @@ impl MKVFile {
         }
     }
 
+    /// Path of the MKV file produced
+    #[must_use]
     pub fn get_outpath(&self) -> &Path {
         self.outpath.as_path()
     }
 
+    /// Path of the merge program used
+    #[must_use]
+    pub fn get_merge_binary(&self) -> &Path {
+        self.merge_binary.as_path()
+    }
+
+    /// Command run to merge the files
+    #[must_use]
     pub const fn get_command(&self) -> &Command {
         &self.command
     }
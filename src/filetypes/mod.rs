@@ -1,10 +1,21 @@
+//! The different file formats we read and write
+
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{
+        BTreeMap,
+        HashMap
+    },
+    fmt::{
+        Display,
+        Formatter
+    },
     fs::File,
     io::{
         prelude::*,
         BufReader,
         BufWriter,
+        Cursor,
         SeekFrom
     },
     path::{
@@ -14,8 +25,19 @@ use std::{
     process::Command
 };
 
+use serde::{
+    Deserialize,
+    Serialize
+};
+
 use crate::{
-    demux::Demuxable,
+    demux::{
+        BoxedSink,
+        Demuxable,
+        StreamId,
+        StreamSink,
+        WriterSink
+    },
     errors::{
         GICSError,
         GICSResult
@@ -26,8 +48,14 @@ use crate::{
     }
 };
 
+include!("adx.rs");
 include!("channel.rs");
 include!("hca.rs");
+include!("hca_encoder.rs");
+include!("ivf.rs");
+include!("matroska.rs");
 include!("mkv.rs");
+include!("subtitles.rs");
 include!("usm.rs");
+include!("utf.rs");
 include!("wav.rs");
\ No newline at end of file
@@ -0,0 +1,398 @@
+/// One timed line of subtitles
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubtitleEntry {
+    /// Time the line appears at, in milliseconds
+    pub start: u64,
+    /// Time the line disappears at, in milliseconds
+    pub end: u64,
+    /// Text of the line, lines separated by `\n`
+    pub text: String
+}
+
+impl SubtitleEntry {
+    /// Decode the payload of a `@SBT` chunk, returning the language channel of the line along with it
+    pub fn from_sbt(payload: &[u8]) -> GICSResult<(u32, Self)> {
+        if payload.len() < 0x14 {
+            return Err(GICSError::new("@SBT payload too short"));
+        }
+        let read = |offset: usize| u32::from_le_bytes([
+            payload[offset], payload[offset + 1], payload[offset + 2], payload[offset + 3]
+        ]);
+        let language = read(0x00);
+        // Times are given in units of 1/time_base seconds
+        let time_base = u64::from(read(0x04)).max(1);
+        let frame_time = u64::from(read(0x08));
+        let duration = u64::from(read(0x0C));
+        let length = read(0x10) as usize;
+        let text: &[u8] = payload.get(0x14..0x14 + length)
+            .ok_or_else(|| GICSError::new("@SBT text exceeds its chunk"))?;
+        // The text is NUL terminated, and sometimes padded
+        let end = text.iter().position(|&c| c == 0).unwrap_or(text.len());
+
+        Ok((language, Self {
+            start: frame_time * 1000 / time_base,
+            end: (frame_time + duration) * 1000 / time_base,
+            text: String::from_utf8_lossy(&text[..end]).replace("\r\n", "\n")
+        }))
+    }
+
+    fn srt_time(ms: u64) -> String {
+        format!("{:02}:{:02}:{:02},{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+    }
+
+    /// Text of a line as ASS wants it : line breaks as `\N`, and SRT styling tags as override codes
+    fn ass_text(text: &str) -> String {
+        let mut res = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(open) = rest.find('<') {
+            let Some(close) = rest[open..].find('>') else { break; };
+            res.push_str(&rest[..open]);
+            let tag = rest[open + 1..open + close].trim().to_ascii_lowercase();
+            match tag.as_str() {
+                "i" | "b" | "u" | "s" => { res.push_str("{\\"); res.push_str(&tag); res.push_str("1}"); },
+                "/i" | "/b" | "/u" | "/s" => { res.push_str("{\\"); res.push_str(&tag[1..]); res.push_str("0}"); },
+                // Fonts and anything else are left to the style
+                _ => {}
+            }
+            rest = &rest[open + close + 1..];
+        }
+        res.push_str(rest);
+        res.replace("\r\n", "\n").replace('\n', "\\N")
+    }
+
+    fn ass_time(ms: u64) -> String {
+        format!("{}:{:02}:{:02}.{:02}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000 / 10)
+    }
+
+    /// Parse a `hh:mm:ss,mmm` SRT time, leniently : a dot is accepted for the milliseconds, hours can be missing,
+    /// milliseconds can have less than three digits (or none) and anything after the time (such as positions) is ignored
+    fn parse_srt_time(time: &str) -> Option<u64> {
+        let time = time.split_whitespace().next()?;
+        let (clock, fraction) = time.split_once([',', '.']).unwrap_or((time, "0"));
+        let fields = clock.split(':').map(|f| f.parse::<u64>().ok()).collect::<Option<Vec<u64>>>()?;
+        let seconds = match fields[..] {
+            [hours, minutes, seconds] => (hours * 60 + minutes) * 60 + seconds,
+            [minutes, seconds] => minutes * 60 + seconds,
+            _ => return None
+        };
+        let millis = fraction.bytes().take_while(u8::is_ascii_digit).take(3)
+            .fold((0, 100), |(millis, unit), digit| (millis + u64::from(digit - b'0') * unit, unit / 10)).0;
+        Some(seconds * 1000 + millis)
+    }
+
+    /// Parse a `h:mm:ss.cc` ASS time
+    fn parse_ass_time(time: &str) -> Option<u64> {
+        let (clock, centis) = time.trim().split_once('.')?;
+        let mut fields = clock.split(':').map(|f| f.trim().parse::<u64>().ok());
+        let (hours, minutes, seconds) = (fields.next()??, fields.next()??, fields.next()??);
+        Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + centis.trim().parse::<u64>().ok()? * 10)
+    }
+}
+
+/// Language codes used in the names of Genshin's subtitle files, with their ISO 639-2 code and name
+const GENSHIN_SUBTITLE_LANGUAGES: [(&str, &str, &str); 15] = [
+    ("CHS", "chi", "Chinese (Simplified)"),
+    ("CHT", "chi", "Chinese (Traditional)"),
+    ("DE", "ger", "German"),
+    ("EN", "eng", "English"),
+    ("ES", "spa", "Spanish"),
+    ("FR", "fre", "French"),
+    ("ID", "ind", "Indonesian"),
+    ("IT", "ita", "Italian"),
+    ("JP", "jpn", "Japanese"),
+    ("KR", "kor", "Korean"),
+    ("PT", "por", "Portuguese"),
+    ("RU", "rus", "Russian"),
+    ("TH", "tha", "Thai"),
+    ("TR", "tur", "Turkish"),
+    ("VI", "vie", "Vietnamese")
+];
+
+/// Subtitles of a cutscene in one language
+#[derive(Clone, Debug, Default)]
+pub struct SubtitleTrack {
+    /// Language channel of the track, as numbered in the USM file
+    pub language: u32,
+    /// Lines of the track, in order of appearance
+    pub entries: Vec<SubtitleEntry>
+}
+
+/// Look of the ASS subtitles we write, turned into the header of the script
+#[derive(Clone, Debug)]
+pub struct ASSStyle {
+    /// Width of the canvas the other values are relative to
+    pub play_res_x: u32,
+    /// Height of the canvas the other values are relative to
+    pub play_res_y: u32,
+    /// Name of the font
+    pub font_name: String,
+    /// Size of the font
+    pub font_size: u32,
+    /// Whether the text is bold
+    pub bold: bool,
+    /// Colour of the text, as `&HAABBGGRR`
+    pub primary_colour: String,
+    /// Colour of the outline of the text, as `&HAABBGGRR`
+    pub outline_colour: String,
+    /// Colour of the shadow of the text, as `&HAABBGGRR`
+    pub back_colour: String,
+    /// Width of the outline
+    pub outline: u32,
+    /// Depth of the shadow
+    pub shadow: u32,
+    /// Position of the text, as on a numeric keypad (2 is bottom center)
+    pub alignment: u8,
+    /// Left margin
+    pub margin_l: u32,
+    /// Right margin
+    pub margin_r: u32,
+    /// Vertical margin
+    pub margin_v: u32
+}
+
+impl Default for ASSStyle {
+    fn default() -> Self {
+        Self {
+            play_res_x: 1920,
+            play_res_y: 1080,
+            font_name: "Arial".into(),
+            font_size: 72,
+            bold: false,
+            primary_colour: "&H00FFFFFF".into(),
+            outline_colour: "&H00000000".into(),
+            back_colour: "&H00000000".into(),
+            outline: 3,
+            shadow: 0,
+            alignment: 2,
+            margin_l: 20,
+            margin_r: 20,
+            margin_v: 60
+        }
+    }
+}
+
+impl ASSStyle {
+    /// Header of the script, up to the format line of its events
+    #[must_use]
+    pub fn header(&self) -> String {
+        format!("[Script Info]
+ScriptType: v4.00+
+WrapStyle: 0
+ScaledBorderAndShadow: yes
+PlayResX: {}
+PlayResY: {}
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding
+Style: Default,{},{},{},&H000000FF,{},{},{},0,0,0,100,100,0,0,1,{},{},{},{},{},{},1
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+",
+            self.play_res_x, self.play_res_y,
+            self.font_name, self.font_size, self.primary_colour, self.outline_colour, self.back_colour,
+            if self.bold { -1 } else { 0 },
+            self.outline, self.shadow, self.alignment, self.margin_l, self.margin_r, self.margin_v)
+    }
+}
+
+impl SubtitleTrack {
+    /// Start an empty track for a language channel
+    #[must_use]
+    pub const fn new(language: u32) -> Self {
+        Self { language, entries: Vec::new() }
+    }
+
+    /// ISO 639-2 code and name of the language of a Genshin subtitle file, from the code ending its name (`EN`, `CHS`...)
+    #[must_use]
+    pub fn genshin_language(code: &str) -> Option<(&'static str, &'static str)> {
+        GENSHIN_SUBTITLE_LANGUAGES.iter()
+            .find(|(known, _, _)| known.eq_ignore_ascii_case(code))
+            .map(|&(_, iso, name)| (iso, name))
+    }
+
+    /// Read a SRT track.
+    ///
+    /// Genshin's SRT files are read leniently : byte order marks, CRLF line endings, missing or odd sequence numbers,
+    /// missing blank lines and overlapping cues are all accepted
+    pub fn read_srt<R: Read>(mut reader: R, language: u32) -> GICSResult<Self> {
+        let mut content: Vec<u8> = Vec::new();
+        reader.read_to_end(&mut content)?;
+        let content = String::from_utf8_lossy(&content);
+
+        let mut track = Self::new(language);
+        let mut current: Option<SubtitleEntry> = None;
+        for line in content.lines() {
+            let line = line.trim_start_matches('\u{FEFF}').trim_end();
+            if let Some((start, end)) = line.split_once("-->") {
+                let (Some(start), Some(end)) = (SubtitleEntry::parse_srt_time(start), SubtitleEntry::parse_srt_time(end)) else {
+                    return Err(GICSError::new(&format!("Invalid SRT time line \"{line}\"")));
+                };
+                if let Some(mut entry) = current.take() {
+                    // Without a blank line before it, the sequence number ended up in the previous text
+                    if let Some((text, number)) = entry.text.rsplit_once('\n') {
+                        if number.chars().all(|c| c.is_ascii_digit()) {
+                            entry.text.truncate(text.len());
+                        }
+                    } else if entry.text.chars().all(|c| c.is_ascii_digit()) {
+                        entry.text.clear();
+                    }
+                    track.entries.push(entry);
+                }
+                current = Some(SubtitleEntry { start, end: end.max(start), text: String::new() });
+            } else if line.is_empty() {
+                track.entries.extend(current.take());
+            } else if let Some(entry) = current.as_mut() {
+                if !entry.text.is_empty() {
+                    entry.text.push('\n');
+                }
+                entry.text.push_str(line);
+            }
+            // Anything outside of an entry is a sequence number, whatever its value
+        }
+        track.entries.extend(current);
+        // Cues can come in any order, and overlap : they are shown together
+        track.entries.retain(|e| !e.text.is_empty());
+        track.entries.sort_by_key(|e| e.start);
+        Ok(track)
+    }
+
+    /// Read the SRT file at `path`
+    pub fn open_srt(path: &Path, language: u32) -> GICSResult<Self> {
+        Self::read_srt(BufReader::new(File::open(path)?), language)
+    }
+
+    /// Convert the SRT file at `srt_path` to an ASS file at `ass_path`, with the given style
+    pub fn convert_srt(srt_path: &Path, ass_path: &Path, style: &ASSStyle) -> GICSResult<()> {
+        let mut writer = BufWriter::new(File::create(ass_path)?);
+        Self::open_srt(srt_path, 0)?.write_ass(&mut writer, style)
+    }
+
+    /// Write the track as SRT
+    pub fn write_srt<W: Write>(&self, writer: &mut W) -> GICSResult<()> {
+        for (index, entry) in self.entries.iter().enumerate() {
+            write!(writer, "{}\r\n{} --> {}\r\n{}\r\n\r\n",
+                index + 1,
+                SubtitleEntry::srt_time(entry.start),
+                SubtitleEntry::srt_time(entry.end),
+                entry.text.replace('\n', "\r\n"))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write the track as ASS, with the given style
+    pub fn write_ass<W: Write>(&self, writer: &mut W, style: &ASSStyle) -> GICSResult<()> {
+        writer.write_all(style.header().as_bytes())?;
+        for entry in &self.entries {
+            writeln!(writer, "Dialogue: 0,{},{},Default,,0,0,0,,{}",
+                SubtitleEntry::ass_time(entry.start),
+                SubtitleEntry::ass_time(entry.end),
+                SubtitleEntry::ass_text(&entry.text))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write the track to `path`, as ASS (with the given style) if its extension is `ass` and as SRT otherwise
+    pub fn write_to(&self, path: &Path, style: &ASSStyle) -> GICSResult<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if path.extension().and_then(std::ffi::OsStr::to_str) == Some("ass") {
+            self.write_ass(&mut writer, style)
+        } else {
+            self.write_srt(&mut writer)
+        }
+    }
+}
+
+/// Sink gathering the `@SBT` chunks of a USM file into one track per language
+pub struct SubtitleSink<'a> {
+    tracks: &'a RefCell<Vec<SubtitleTrack>>
+}
+
+impl<'a> SubtitleSink<'a> {
+    /// Gather the subtitles into `tracks`
+    #[must_use]
+    pub const fn new(tracks: &'a RefCell<Vec<SubtitleTrack>>) -> Self {
+        Self { tracks }
+    }
+}
+
+impl StreamSink for SubtitleSink<'_> {
+    fn write_chunk(&mut self, chunk: &USMChunk) -> GICSResult<()> {
+        let (language, entry) = SubtitleEntry::from_sbt(&chunk.payload)?;
+        let mut tracks = self.tracks.borrow_mut();
+        if let Some(track) = tracks.iter_mut().find(|t| t.language == language) {
+            track.entries.push(entry);
+        } else {
+            let mut track = SubtitleTrack::new(language);
+            track.entries.push(entry);
+            tracks.push(track);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> GICSResult<()> {
+        self.tracks.borrow_mut().iter_mut().for_each(|t| t.entries.sort_by_key(|e| e.start));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod subtitles_tests {
+    use super::*;
+
+    fn entry(start: u64, end: u64, text: &str) -> SubtitleEntry {
+        SubtitleEntry { start, end, text: text.into() }
+    }
+
+    #[test]
+    fn srt_times() {
+        assert_eq!(SubtitleEntry::parse_srt_time("01:02:03,456"), Some(3_723_456));
+        assert_eq!(SubtitleEntry::parse_srt_time(" 00:00:01.5 X1:10 X2:20"), Some(1500));
+        assert_eq!(SubtitleEntry::parse_srt_time("02:03,04"), Some(123_040));
+        assert_eq!(SubtitleEntry::parse_srt_time("00:00:07,"), Some(7000));
+        assert_eq!(SubtitleEntry::parse_srt_time("00:00:07"), Some(7000));
+        assert_eq!(SubtitleEntry::parse_srt_time("00:00:07,1234"), Some(7123));
+        assert_eq!(SubtitleEntry::parse_srt_time("00:00:07,２５０"), Some(7000));
+        assert_eq!(SubtitleEntry::parse_srt_time("00:00:07,2é"), Some(7200));
+        assert_eq!(SubtitleEntry::parse_srt_time("7"), None);
+        assert_eq!(SubtitleEntry::parse_srt_time("aa:00:07,000"), None);
+    }
+
+    #[test]
+    fn srt_with_bom_and_crlf() {
+        let srt = "\u{FEFF}1\r\n00:00:01,000 --> 00:00:02,500\r\nHello\r\nworld\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\nBye\r\n";
+        let track = SubtitleTrack::read_srt(srt.as_bytes(), 3).unwrap();
+        assert_eq!(track.language, 3);
+        assert_eq!(track.entries, [entry(1000, 2500, "Hello\nworld"), entry(3000, 4000, "Bye")]);
+    }
+
+    #[test]
+    fn srt_with_odd_numbering() {
+        // Numbers out of order, missing, and a missing blank line leaving the next number in the text
+        let srt = "7\n00:00:01,000 --> 00:00:02,000\nOne\n3\n00:00:02,000 --> 00:00:03,000\nTwo\n\n00:00:03,000 --> 00:00:04,000\nThree\n";
+        let track = SubtitleTrack::read_srt(srt.as_bytes(), 0).unwrap();
+        assert_eq!(track.entries, [entry(1000, 2000, "One"), entry(2000, 3000, "Two"), entry(3000, 4000, "Three")]);
+    }
+
+    #[test]
+    fn srt_with_overlaps() {
+        let srt = "1\n00:00:05,000 --> 00:00:08,000\nLater\n\n2\n00:00:01,000 --> 00:00:06,000\nEarlier\n\n3\n00:00:04,000 --> 00:00:03,000\nBackwards\n";
+        let track = SubtitleTrack::read_srt(srt.as_bytes(), 0).unwrap();
+        assert_eq!(track.entries, [entry(1000, 6000, "Earlier"), entry(4000, 4000, "Backwards"), entry(5000, 8000, "Later")]);
+    }
+
+    #[test]
+    fn srt_round_trip() {
+        let track = SubtitleTrack { language: 0, entries: vec![entry(1000, 2500, "Hello\nworld"), entry(3_723_456, 3_724_000, "Bye")] };
+        let mut srt: Vec<u8> = Vec::new();
+        track.write_srt(&mut srt).unwrap();
+        assert_eq!(SubtitleTrack::read_srt(srt.as_slice(), 0).unwrap().entries, track.entries);
+    }
+
+    #[test]
+    fn srt_with_invalid_time() {
+        assert!(SubtitleTrack::read_srt(&b"1\n00:00:01 --> soon\nText\n"[..], 0).is_err());
+    }
+}
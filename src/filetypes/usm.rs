@@ -1,4 +1,3 @@
-
 // The default for everything here is 0 (or 0.0)
 #[derive(Default)]
 struct USMInfo {
@@ -12,24 +11,439 @@ struct USMInfo {
     frame_rate: u32
 }
 
-#[allow(dead_code)]
-pub struct USMFile {
-    filename: String,
-    path: PathBuf,
-    key1: [u8; 4],
-    key2: [u8; 4],
+impl USMInfo {
+    fn parse(byte_block: &[u8; 0x20]) -> Self {
+        Self {
+            sig: make_be32(&byte_block[0..4]),
+            data_size: make_be32(&byte_block[4..8]),
+            data_offset: byte_block[9],
+            padding_size: make_be16(&byte_block[10..12]),
+            chno: byte_block[12],
+            data_type: byte_block[15],
+            frame_time: make_be32(&byte_block[16..20]),
+            frame_rate: make_be32(&byte_block[20..24])
+        }
+    }
+
+    /// Size of the payload carried by the chunk
+    fn payload_size(&self) -> GICSResult<usize> {
+        self.data_size
+            .checked_sub(u32::from(self.data_offset))
+            .and_then(|s| s.checked_sub(u32::from(self.padding_size)))
+            .map(|s| s as usize)
+            .ok_or_else(|| GICSError::new("Invalid USM chunk : payload offset and padding exceed its size"))
+    }
+}
+
+/// Kind of a USM chunk, given by its signature
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum ChunkKind {
+    /// `CRID` chunk, describing the streams of the file
+    Info,
+    /// `@SFV` chunk, carrying video
+    Video,
+    /// `@SFA` chunk, carrying audio
+    Audio,
+    /// `@ALP` chunk, carrying the alpha channel of a video
+    Alpha,
+    /// `@SBT` chunk, carrying subtitles
+    Subtitle,
+    /// Any other signature, kept as is
+    Unknown(u32)
+}
+
+impl Display for ChunkKind {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Info => write!(fmt, "CRID"),
+            Self::Video => write!(fmt, "@SFV"),
+            Self::Audio => write!(fmt, "@SFA"),
+            Self::Alpha => write!(fmt, "@ALP"),
+            Self::Subtitle => write!(fmt, "@SBT"),
+            Self::Unknown(sig) => write!(fmt, "{}", String::from_utf8_lossy(&sig.to_be_bytes()))
+        }
+    }
+}
+
+impl From<u32> for ChunkKind {
+    fn from(sig: u32) -> Self {
+        match sig {
+            0x4352_4944 => Self::Info,
+            0x4053_4656 => Self::Video,
+            0x4053_4641 => Self::Audio,
+            0x4041_4C50 => Self::Alpha,
+            0x4053_4254 => Self::Subtitle,
+            other => Self::Unknown(other)
+        }
+    }
+}
+
+/// One chunk of a USM file, with its payload
+#[derive(Clone, Debug)]
+pub struct USMChunk {
+    /// Kind of the chunk
+    pub kind: ChunkKind,
+    /// Channel number of the stream the chunk belongs to
+    pub chno: u8,
+    /// Type of the data : 0 for stream data, 1 for headers, 2 for section ends, 3 for metadata
+    pub data_type: u8,
+    /// Time of the chunk, in units of `1/frame_rate` hundredths of a second
+    pub frame_time: u32,
+    /// Frame rate of the stream, in hundredths of frames per second
+    pub frame_rate: u32,
+    /// Content of the chunk, decrypted if it was stream data
+    pub payload: Vec<u8>
+}
+
+impl USMChunk {
+    /// Whether the chunk carries elementary stream data (as opposed to headers or metadata)
+    #[must_use]
+    pub const fn is_stream_data(&self) -> bool {
+        self.data_type == 0
+    }
+}
+
+/// Information on one stream, from the `CRID` table of a USM file
+#[derive(Clone, Debug, Serialize)]
+pub struct StreamEntry {
+    /// Name of the file the stream was built from
+    pub filename: String,
+    /// Size of the source file
+    pub filesize: u64,
+    /// Kind of the chunks carrying the stream
+    pub kind: ChunkKind,
+    /// Channel number of the stream
+    pub chno: u8,
+    /// Average bitrate of the stream, in bits per second
+    pub avbps: u64
+}
+
+/// Video stream information, from its `VIDEO_HDRINFO` header
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct VideoInfo {
+    /// Channel number of the stream
+    pub chno: u8,
+    /// Width of the encoded picture
+    pub width: u32,
+    /// Height of the encoded picture
+    pub height: u32,
+    /// Width of the displayed picture
+    pub display_width: u32,
+    /// Height of the displayed picture
+    pub display_height: u32,
+    /// Number of frames in the stream
+    pub total_frames: u32,
+    /// Numerator of the frame rate
+    pub framerate_n: u32,
+    /// Denominator of the frame rate
+    pub framerate_d: u32,
+    /// CRI codec identifier (1 for MPEG-1, 5 for H.264, 9 for VP9)
+    pub codec: u32,
+    /// Kind of alpha channel carried alongside the video (0 for none)
+    pub alpha_type: u32
+}
+
+impl VideoInfo {
+    /// Frame rate, in frames per second
+    #[must_use]
+    pub fn frame_rate(&self) -> Option<f64> {
+        (self.framerate_d != 0).then(|| f64::from(self.framerate_n) / f64::from(self.framerate_d))
+    }
+
+    /// Name of the codec
+    #[must_use]
+    pub const fn codec_name(&self) -> &'static str {
+        match self.codec {
+            1 => "MPEG-1",
+            5 => "H.264",
+            9 => "VP9",
+            _ => "unknown"
+        }
+    }
+}
+
+/// Audio stream information, from its `AUDIO_HDRINFO` header
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AudioInfo {
+    /// Channel number of the stream
+    pub chno: u8,
+    /// CRI codec identifier (2 for ADX, 4 for HCA)
+    pub codec: u32,
+    /// Sampling rate, in Hz
+    pub sampling_rate: u32,
+    /// Number of audio channels
+    pub channel_count: u32
+}
+
+impl AudioInfo {
+    /// CRI codec identifier of ADX streams
+    pub const ADX: u32 = 2;
+    /// CRI codec identifier of HCA streams
+    pub const HCA: u32 = 4;
+
+    /// Name of the codec
+    #[must_use]
+    pub const fn codec_name(&self) -> &'static str {
+        match self.codec {
+            Self::ADX => "ADX",
+            Self::HCA => "HCA",
+            _ => "unknown"
+        }
+    }
+
+    /// Whether the stream is ADX, rather than HCA
+    #[must_use]
+    pub const fn is_adx(&self) -> bool {
+        self.codec == Self::ADX
+    }
+
+    /// Extension of the files the stream is demuxed to
+    #[must_use]
+    pub const fn extension(&self) -> &'static str {
+        if self.is_adx() { "adx" } else { "hca" }
+    }
+
+    /// Whether the audio layer of the stream is masked in encrypted files.
+    ///
+    /// HCA streams carry their own cipher and are stored as is; ADX streams are masked by the container
+    #[must_use]
+    pub const fn is_masked(&self) -> bool {
+        self.is_adx()
+    }
+}
+
+/// Stream metadata of a USM file, gathered from the @UTF tables of its `CRID` and header chunks
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct USMMetadata {
+    /// Name of the file the USM was built from
+    pub filename: Option<String>,
+    /// Streams listed in the `CRID` table
+    pub streams: Vec<StreamEntry>,
+    /// Headers of the video streams
+    pub video: Vec<VideoInfo>,
+    /// Headers of the audio streams
+    pub audio: Vec<AudioInfo>
+}
+
+impl USMMetadata {
+    /// Gather whatever metadata a chunk carries. Stream data is ignored
+    pub fn feed(&mut self, chunk: &USMChunk) -> GICSResult<()> {
+        // Only headers carry the tables we want
+        let wanted = match chunk.kind {
+            ChunkKind::Info => true,
+            ChunkKind::Video | ChunkKind::Audio => chunk.data_type == 1,
+            ChunkKind::Alpha | ChunkKind::Subtitle | ChunkKind::Unknown(_) => false
+        };
+        if !wanted || !chunk.payload.starts_with(b"@UTF") {
+            return Ok(());
+        }
+        let table = UTFTable::parse(&chunk.payload)?;
+        let get = |row: usize, column: &str| -> u32 {
+            table.get_u64(row, column).and_then(|v| v.try_into().ok()).unwrap_or(0)
+        };
+        match table.name() {
+            "CRIUSF_DIR_STREAM" => {
+                for row in 0..table.row_count() {
+                    let stmid = get(row, "stmid");
+                    let filename: String = table.get_str(row, "filename").unwrap_or_default().into();
+                    if stmid == 0 {
+                        // This row describes the whole file
+                        self.filename = Some(filename);
+                        continue;
+                    }
+                    self.streams.push(StreamEntry {
+                        filename,
+                        filesize: table.get_u64(row, "filesize").unwrap_or(0),
+                        kind: ChunkKind::from(stmid),
+                        chno: get(row, "chno") as u8,
+                        avbps: table.get_u64(row, "avbps").unwrap_or(0)
+                    });
+                }
+            },
+            "VIDEO_HDRINFO" => (0..table.row_count()).for_each(|row| self.video.push(VideoInfo {
+                chno: chunk.chno,
+                width: get(row, "width"),
+                height: get(row, "height"),
+                display_width: get(row, "disp_width"),
+                display_height: get(row, "disp_height"),
+                total_frames: get(row, "total_frames"),
+                framerate_n: get(row, "framerate_n"),
+                framerate_d: get(row, "framerate_d"),
+                codec: get(row, "mpeg_codec"),
+                alpha_type: get(row, "alpha_type")
+            })),
+            "AUDIO_HDRINFO" => (0..table.row_count()).for_each(|row| self.audio.push(AudioInfo {
+                chno: chunk.chno,
+                codec: get(row, "audio_codec"),
+                sampling_rate: get(row, "sampling_rate"),
+                channel_count: get(row, "num_channels")
+            })),
+            _ => { /* Seek tables and such, we don't need them */ }
+        }
+        Ok(())
+    }
+
+    /// Header of the video stream on channel `chno`
+    #[must_use]
+    pub fn video_info(&self, chno: u8) -> Option<&VideoInfo> {
+        self.video.iter().find(|v| v.chno == chno)
+    }
+
+    /// Header of the audio stream on channel `chno`
+    #[must_use]
+    pub fn audio_info(&self, chno: u8) -> Option<&AudioInfo> {
+        self.audio.iter().find(|a| a.chno == chno)
+    }
+}
+
+/// Statistics on one stream of a USM file, gathered by [`USMProbe`]
+#[derive(Clone, Debug, Serialize)]
+pub struct StreamProbe {
+    /// Kind of the chunks carrying the stream
+    pub kind: ChunkKind,
+    /// Channel number of the stream
+    pub chno: u8,
+    /// Number of stream data chunks
+    pub chunks: u64,
+    /// Total size of the stream data payloads
+    pub payload_size: u64,
+    /// Frame rate announced by the chunks, in hundredths of frames per second
+    pub frame_rate: u32,
+    /// Duration of the stream in seconds, derived from the time of its chunks
+    pub duration: Option<f64>,
+    #[serde(skip)]
+    last_frame_time: u32,
+    #[serde(skip)]
+    last_step: u32
+}
+
+impl StreamProbe {
+    const fn new(kind: ChunkKind, chno: u8) -> Self {
+        Self {
+            kind,
+            chno,
+            chunks: 0,
+            payload_size: 0,
+            frame_rate: 0,
+            duration: None,
+            last_frame_time: 0,
+            last_step: 0
+        }
+    }
+
+    fn account(&mut self, chunk: &USMChunk) {
+        if self.chunks > 0 && chunk.frame_time > self.last_frame_time {
+            self.last_step = chunk.frame_time - self.last_frame_time;
+        }
+        self.chunks += 1;
+        self.payload_size += chunk.payload.len() as u64;
+        self.frame_rate = chunk.frame_rate;
+        self.last_frame_time = self.last_frame_time.max(chunk.frame_time);
+        // The last chunk lasts as long as the one before it
+        self.duration = (self.frame_rate != 0).then(||
+            (f64::from(self.last_frame_time) + f64::from(self.last_step)) / f64::from(self.frame_rate)
+        );
+    }
+}
+
+/// Layout of a USM file : its streams, their sizes and durations, and its metadata
+#[derive(Clone, Debug, Serialize)]
+pub struct USMProbe {
+    /// Name of the probed file
+    pub filename: String,
+    /// Size of the probed file
+    pub size: u64,
+    /// Number of chunks in the file, of any kind
+    pub chunks: u64,
+    /// Every stream carrying data, in order of appearance
+    pub streams: Vec<StreamProbe>,
+    /// Metadata from the `CRID` and header chunks
+    pub metadata: USMMetadata
+}
+
+impl USMProbe {
+    /// Probe the USM file at `path`, without decrypting or writing anything
+    pub fn from_path(path: &Path) -> GICSResult<Self> {
+        let filename: String = path
+            .file_name().ok_or_else(|| GICSError::new("USM Path has no file name"))?
+            .to_string_lossy().into();
+        Self::from_reader(filename, BufReader::new(File::open(path)?))
+    }
+
+    /// Probe a USM file read from any seekable source
+    pub fn from_reader<R: Read + Seek>(filename: String, mut reader: R) -> GICSResult<Self> {
+        let start = reader.stream_position()?;
+        let size = reader.seek(SeekFrom::End(0))? - start;
+        reader.seek(SeekFrom::Start(start))?;
+
+        let mut res = Self {
+            filename,
+            size,
+            chunks: 0,
+            streams: Vec::new(),
+            metadata: USMMetadata::default()
+        };
+        for chunk in USMReader::without_keys(reader)? {
+            let chunk = chunk?;
+            res.chunks += 1;
+            if !chunk.is_stream_data() {
+                res.metadata.feed(&chunk)?;
+                continue;
+            }
+            if let Some(stream) = res.streams.iter_mut().find(|s| s.kind == chunk.kind && s.chno == chunk.chno) {
+                stream.account(&chunk);
+            } else {
+                let mut stream = StreamProbe::new(chunk.kind, chunk.chno);
+                stream.account(&chunk);
+                res.streams.push(stream);
+            }
+        }
+        Ok(res)
+    }
+}
+
+impl Display for USMProbe {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(fmt, "{} ({} bytes, {} chunks)", self.filename, self.size, self.chunks)?;
+        if let Some(source) = &self.metadata.filename {
+            writeln!(fmt, "  Source file : {source}")?;
+        }
+        for stream in &self.streams {
+            write!(fmt, "  {} #{} : {} chunks, {} bytes", stream.kind, stream.chno, stream.chunks, stream.payload_size)?;
+            if let Some(duration) = stream.duration {
+                write!(fmt, ", {duration:.3}s")?;
+            }
+            writeln!(fmt)?;
+            match stream.kind {
+                ChunkKind::Video => if let Some(video) = self.metadata.video_info(stream.chno) {
+                    writeln!(fmt, "    {} {}x{} (displayed {}x{}), {:.3} fps, {} frames",
+                        video.codec_name(), video.width, video.height, video.display_width, video.display_height,
+                        video.frame_rate().unwrap_or_default(), video.total_frames)?;
+                },
+                ChunkKind::Audio => if let Some(audio) = self.metadata.audio_info(stream.chno) {
+                    writeln!(fmt, "    {}, {} Hz, {} channels", audio.codec_name(), audio.sampling_rate, audio.channel_count)?;
+                },
+                _ => {}
+            }
+        }
+        for entry in &self.metadata.streams {
+            writeln!(fmt, "  CRID entry {} #{} : \"{}\" ({} bytes, {} bps)", entry.kind, entry.chno, entry.filename, entry.filesize, entry.avbps)?;
+        }
+        Ok(())
+    }
+}
+
+/// The masks used to decrypt the streams of a USM file
+#[derive(Clone)]
+struct USMMask {
     video_mask_1: [u8; 32],
     video_mask_2: [u8; 32],
     audio_mask: [u8; 32]
 }
 
-impl USMFile {
-    pub fn new(file: PathBuf, key2: [u8; 4], key1: [u8; 4]) -> Self {
+impl USMMask {
+    fn new(key2: [u8; 4], key1: [u8; 4]) -> Self {
         let mut res: Self = Self {
-            filename: file.file_name().unwrap().to_str().unwrap().into(),
-            path: file,
-            key1,
-            key2,
             video_mask_1: [0; 32],
             video_mask_2: [0; 32],
             audio_mask: [0; 32]
@@ -73,7 +487,26 @@ impl USMFile {
         self.video_mask_1[0x1D] = self.video_mask_1[0x03].wrapping_add(self.video_mask_1[0x04]);
         self.video_mask_1[0x1E] = self.video_mask_1[0x05].wrapping_sub(self.video_mask_1[0x16]);
         self.video_mask_1[0x1F] = self.video_mask_1[0x1D] ^ self.video_mask_1[0x13];
+        self.derive_masks();
+    }
+
+    /// Masks of the 7 bytes of a key used : `key1`, then the 3 lower bytes of `key2`
+    fn from_key_bytes(key: [u8; 7]) -> Self {
+        Self::new([key[4], key[5], key[6], 0], [key[0], key[1], key[2], key[3]])
+    }
 
+    /// Masks following from any `video_mask_1`, whether a key gives it or not
+    fn from_video_mask(video_mask_1: [u8; 32]) -> Self {
+        let mut res: Self = Self {
+            video_mask_1,
+            video_mask_2: [0; 32],
+            audio_mask: [0; 32]
+        };
+        res.derive_masks();
+        res
+    }
+
+    fn derive_masks(&mut self) {
         let lookup: [u8; 4] = [ 85, 82, 85, 67 ]; // "URUC"
         for i in 0..0x20 {
             self.video_mask_2[i] = self.video_mask_1[i] ^ 0xFF;
@@ -81,7 +514,16 @@ impl USMFile {
         }
     }
 
-    fn mask_video(&mut self, data: &mut [u8], size: usize) {
+    fn mask_audio(&self, data: &mut [u8]) {
+        let data_offset = 0x140;
+        if data.len() <= data_offset { return; }
+        for (i, byte) in data[data_offset..].iter_mut().enumerate() {
+            *byte ^= self.audio_mask[i & 0x1F];
+        }
+    }
+
+    fn mask_video(&self, data: &mut [u8]) {
+        let size = data.len();
         let data_offset = 0x40;
         if size < data_offset { return; }
         let size = size - data_offset;
@@ -100,13 +542,592 @@ impl USMFile {
     }
 }
 
-impl Demuxable for USMFile {
-    fn demux(mut self, video_extract: bool, audio_extract: bool, output: &Path) -> GICSResult<(PathBuf, Vec<PathBuf>)> {
-        let f = File::open(self.path.as_path())?;
-        let mut file_size = f.metadata()?.len();
-        let mut reader = BufReader::new(f);
-        let mut info: USMInfo = USMInfo::default();
+/// Number of chunks of each stream read to check keys
+const KEY_CHECK_CHUNKS: usize = 8;
+/// Number of HCA blocks decoded to check keys
+pub const KEY_CHECK_BLOCKS: usize = 16;
+
+/// Outcome of checking keys against encrypted content
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum KeyCheck {
+    /// The content decrypts to something valid
+    Valid,
+    /// The content decrypts to something that cannot be valid
+    Mismatch,
+    /// Nothing the keys decrypt could be checked
+    Inconclusive
+}
+
+impl KeyCheck {
+    /// Combine the outcomes of two checks : any mismatch wins over any valid check
+    #[must_use]
+    pub const fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Mismatch, _) | (_, Self::Mismatch) => Self::Mismatch,
+            (Self::Valid, _) | (_, Self::Valid) => Self::Valid,
+            _ => Self::Inconclusive
+        }
+    }
+}
+
+/// When to unmask the audio stream data of an encrypted USM file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AudioMasking {
+    /// Unmask the streams whose header announces a masked codec (see [`AudioInfo::is_masked`])
+    #[default]
+    Auto,
+    /// Unmask every audio stream
+    Always,
+    /// Leave every audio stream as is
+    Never
+}
+
+/// Streaming reader of the chunks of a USM file, over any seekable source.
+///
+/// Video and alpha stream data is decrypted on the fly, as is audio stream data according to the [`AudioMasking`] set;
+/// every other payload is yielded as is
+pub struct USMReader<R: Read + Seek> {
+    reader: R,
+    remaining: u64,
+    mask: Option<USMMask>,
+    audio_masking: AudioMasking,
+    metadata: USMMetadata
+}
+
+impl<R: Read + Seek> USMReader<R> {
+    /// Start reading chunks from the current position of `reader`, up to its end
+    pub fn new(reader: R, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<Self> {
+        let mut res = Self::without_keys(reader)?;
+        res.mask = Some(USMMask::new(key2, key1));
+        Ok(res)
+    }
+
+    /// Start reading chunks from the current position of `reader`, without decrypting anything
+    pub fn without_keys(mut reader: R) -> GICSResult<Self> {
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(Self {
+            reader,
+            remaining: end.saturating_sub(start),
+            mask: None,
+            audio_masking: AudioMasking::default(),
+            metadata: USMMetadata::default()
+        })
+    }
+
+    /// Choose when audio stream data is unmasked. Without keys, nothing is
+    pub const fn set_audio_masking(&mut self, masking: AudioMasking) {
+        self.audio_masking = masking;
+    }
+
+    /// Metadata gathered from the header chunks read so far
+    #[must_use]
+    pub const fn metadata(&self) -> &USMMetadata {
+        &self.metadata
+    }
+
+    /// Get the underlying reader back
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_chunk(&mut self) -> GICSResult<USMChunk> {
+        // Read 32 bits at a time
+        let mut byte_block: [u8; 0x20] = [0; 0x20];
+        self.reader.read_exact(&mut byte_block)?;
+
+        // Parse info from the content
+        let info = USMInfo::parse(&byte_block);
+
+        // Now work with the rest of the data
+        // Read the size of the data
+        let size: usize = info.payload_size()?;
+        let skip = info.data_offset.checked_sub(0x18)
+            .ok_or_else(|| GICSError::new("Invalid USM chunk : payload offset inside the chunk header"))?;
+        self.reader.seek(SeekFrom::Current(i64::from(skip)))?;
+        let mut payload = vec![0u8; size];
+        self.reader.read_exact(&mut payload)?;
+        // Skip padding
+        self.reader.seek(SeekFrom::Current(i64::from(info.padding_size)))?;
+        // Account for it
+        self.remaining = self.remaining.saturating_sub(8 + u64::from(info.data_size));
+
+        let mut chunk = USMChunk {
+            kind: ChunkKind::from(info.sig),
+            chno: info.chno,
+            data_type: info.data_type,
+            frame_time: info.frame_time,
+            frame_rate: info.frame_rate,
+            payload
+        };
+        if !chunk.is_stream_data() {
+            // Metadata only tells us what to unmask, a broken table must not stop the demux
+            let _ = self.metadata.feed(&chunk);
+        } else if let Some(mask) = &self.mask {
+            match chunk.kind {
+                ChunkKind::Video | ChunkKind::Alpha => mask.mask_video(&mut chunk.payload),
+                ChunkKind::Audio if self.unmasks_audio(chunk.chno) => mask.mask_audio(&mut chunk.payload),
+                _ => {}
+            }
+        }
+        Ok(chunk)
+    }
+
+    fn unmasks_audio(&self, chno: u8) -> bool {
+        match self.audio_masking {
+            AudioMasking::Auto => self.metadata.audio_info(chno).is_some_and(AudioInfo::is_masked),
+            AudioMasking::Always => true,
+            AudioMasking::Never => false
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for USMReader<R> {
+    type Item = GICSResult<USMChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let chunk = self.read_chunk();
+        if chunk.is_err() {
+            // Do not try and read past a broken chunk
+            self.remaining = 0;
+        }
+        Some(chunk)
+    }
+}
+
+/// A USM cutscene file, along with the keys used to decrypt it
+pub struct USMFile {
+    filename: String,
+    path: PathBuf,
+    key1: [u8; 4],
+    key2: [u8; 4],
+    audio_masking: AudioMasking
+}
+
+impl USMFile {
+    /// Open a USM file, to be decrypted with the two halves of its key
+    pub fn new(file: PathBuf, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<Self> {
+        let filename: String = file
+            .file_name().ok_or_else(|| GICSError::new("USM path has no file name"))?
+            .to_str().ok_or_else(|| GICSError::new("Unable to decode USM file name to UTF-8"))?
+            .into();
+        Ok(Self {
+            filename,
+            path: file,
+            key1,
+            key2,
+            audio_masking: AudioMasking::default()
+        })
+    }
+
+    /// Choose when audio stream data is unmasked
+    pub const fn set_audio_masking(&mut self, masking: AudioMasking) {
+        self.audio_masking = masking;
+    }
+
+    /// Name of the file
+    #[must_use]
+    pub fn get_filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Read the stream metadata of the file.
+    ///
+    /// Only the headers, at the start of the file, are read
+    pub fn metadata(&self) -> GICSResult<USMMetadata> {
+        let mut metadata = USMMetadata::default();
+        for chunk in self.chunks()? {
+            let chunk = chunk?;
+            if chunk.is_stream_data() {
+                break;
+            }
+            metadata.feed(&chunk)?;
+        }
+        Ok(metadata)
+    }
+
+    /// Read the subtitles embedded in the file, one track per language
+    pub fn subtitles(&self) -> GICSResult<Vec<SubtitleTrack>> {
+        let tracks: RefCell<Vec<SubtitleTrack>> = RefCell::new(Vec::new());
+        let mut sink = SubtitleSink::new(&tracks);
+        for chunk in self.chunks()? {
+            let chunk = chunk?;
+            if chunk.kind == ChunkKind::Subtitle && chunk.is_stream_data() {
+                sink.write_chunk(&chunk)?;
+            }
+        }
+        sink.finish()?;
+        Ok(tracks.into_inner())
+    }
+
+    /// Read the chunks of the file one by one
+    pub fn chunks(&self) -> GICSResult<USMReader<BufReader<File>>> {
+        let mut reader = USMReader::new(BufReader::new(File::open(self.path.as_path())?), self.key2, self.key1)?;
+        reader.set_audio_masking(self.audio_masking);
+        Ok(reader)
+    }
+
+    /// Read the encrypted start of the first video and audio channels, to check keys against
+    pub fn key_sample(&self) -> GICSResult<KeySample> {
+        self.sample(KEY_CHECK_CHUNKS)
+    }
+
+    fn sample(&self, chunks: usize) -> GICSResult<KeySample> {
+        let metadata = self.metadata()?;
+        let mut sample = KeySample {
+            video: Vec::new(),
+            audio: Vec::new(),
+            // ADX streams have no key check of their own
+            hca_audio: !metadata.audio.is_empty() && !metadata.audio_info(0).is_some_and(AudioInfo::is_masked),
+            audio_masked: match self.audio_masking {
+                AudioMasking::Auto => metadata.audio_info(0).is_some_and(AudioInfo::is_masked),
+                AudioMasking::Always => true,
+                AudioMasking::Never => false
+            }
+        };
+        for chunk in USMReader::without_keys(BufReader::new(File::open(self.path.as_path())?))? {
+            let chunk = chunk?;
+            if !chunk.is_stream_data() || chunk.chno != 0 {
+                continue;
+            }
+            match chunk.kind {
+                ChunkKind::Video if sample.video.len() < chunks => sample.video.push(chunk.payload),
+                ChunkKind::Audio if sample.audio.len() < chunks => sample.audio.push(chunk.payload),
+                _ => {}
+            }
+            if sample.video.len() == chunks && (sample.audio.len() == chunks || metadata.audio.is_empty()) {
+                break;
+            }
+        }
+        Ok(sample)
+    }
+
+    /// Check the keys against the start of the first video and audio channels, without demuxing the whole file
+    pub fn verify_key(&self) -> GICSResult<KeyCheck> {
+        self.key_sample()?.verify(self.key2, self.key1)
+    }
+
+    /// Recover the key of the file, whatever keys it was opened with, from known plaintext at the start of its
+    /// first video and audio channels
+    pub fn recover_key(&self) -> GICSResult<KeyRecovery> {
+        self.sample(KEY_RECOVERY_CHUNKS)?.recover_key()
+    }
+}
+
+/// Number of chunks of each stream read to recover keys
+const KEY_RECOVERY_CHUNKS: usize = 128;
+/// Most keys checked against the content once known plaintext runs out
+const KEY_RECOVERY_CANDIDATES: usize = 0x100;
+
+/// Outcome of a key recovery
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyRecovery {
+    /// The key, as `(key2, key1)`, checked against the content
+    Found(u32, u32),
+    /// Known plaintext ran out before the whole key was found : the bytes of `video_mask_1` solved so far
+    Partial([Option<u8>; 32])
+}
+
+/// Votes for the values of every byte of `video_mask_1`, from known plaintext
+type MaskHints = [BTreeMap<u8, usize>; 32];
+
+/// Bytes of a key solved from the values of `video_mask_1` they lead to
+struct MaskSolver {
+    /// `key1`, then the 3 lower bytes of `key2` (the highest one is not used by the masks)
+    key: [Option<u8>; 7],
+    /// Bytes of the key every byte of `video_mask_1` depends on, as bit fields
+    dependencies: [u8; 32]
+}
+
+impl MaskSolver {
+    fn new() -> Self {
+        let mut dependencies: [u8; 32] = [0; 32];
+        for background in [0x00, 0xA5] {
+            let base = USMMask::from_key_bytes([background; 7]).video_mask_1;
+            for byte in 0..7 {
+                for value in 0..=255 {
+                    let mut key: [u8; 7] = [background; 7];
+                    key[byte] = value;
+                    let mask = USMMask::from_key_bytes(key).video_mask_1;
+                    for (position, dependency) in dependencies.iter_mut().enumerate() {
+                        if mask[position] != base[position] {
+                            *dependency |= 1 << byte;
+                        }
+                    }
+                }
+            }
+        }
+        Self { key: [None; 7], dependencies }
+    }
+
+    fn unknown(&self, position: usize) -> Vec<usize> {
+        (0..7).filter(|&byte| self.dependencies[position] >> byte & 1 == 1 && self.key[byte].is_none()).collect()
+    }
 
+    /// Bytes of `video_mask_1` following from the bytes of the key solved so far
+    fn video_mask(&self) -> [Option<u8>; 32] {
+        let mask = USMMask::from_key_bytes(self.key.map(|byte| byte.unwrap_or(0))).video_mask_1;
+        std::array::from_fn(|position| self.unknown(position).is_empty().then_some(mask[position]))
+    }
+
+    /// Most voted value of a byte of `video_mask_1`, if it wins outright
+    fn consensus(votes: &BTreeMap<u8, usize>) -> Option<u8> {
+        let (&value, &count) = votes.iter().max_by_key(|(_, &count)| count)?;
+        (votes.values().filter(|&&other| other == count).count() == 1).then_some(value)
+    }
+
+    /// Solve every byte of the key that is the only unknown behind a voted byte of `video_mask_1`
+    fn solve(&mut self, hints: &MaskHints) -> bool {
+        let mut progress = false;
+        for (position, votes) in hints.iter().enumerate() {
+            let Some(value) = Self::consensus(votes) else { continue; };
+            let [byte] = self.unknown(position)[..] else { continue; };
+            let mut key = self.key.map(|byte| byte.unwrap_or(0));
+            let solutions: Vec<u8> = (0..=255).filter(|&candidate| {
+                key[byte] = candidate;
+                USMMask::from_key_bytes(key).video_mask_1[position] == value
+            }).collect();
+            if let [solution] = solutions[..] {
+                self.key[byte] = Some(solution);
+                progress = true;
+            }
+        }
+        progress
+    }
+
+    /// Every key agreeing with the votes, if few enough bytes are left to try them all
+    fn candidates(&self, hints: &MaskHints) -> Vec<[u8; 7]> {
+        let unknown: Vec<usize> = (0..7).filter(|&byte| self.key[byte].is_none()).collect();
+        if unknown.len() > 2 {
+            return Vec::new();
+        }
+        let expected: Vec<(usize, u8)> = hints.iter().enumerate()
+            .filter_map(|(position, votes)| Self::consensus(votes).map(|value| (position, value)))
+            .collect();
+        let mut res: Vec<[u8; 7]> = Vec::new();
+        for combination in 0..1_usize << (8 * unknown.len()) {
+            let mut key = self.key.map(|byte| byte.unwrap_or(0));
+            for (index, &byte) in unknown.iter().enumerate() {
+                key[byte] = (combination >> (8 * index)) as u8;
+            }
+            let mask = USMMask::from_key_bytes(key).video_mask_1;
+            if expected.iter().all(|&(position, value)| mask[position] == value) {
+                res.push(key);
+            }
+        }
+        res
+    }
+}
+
+/// Encrypted start of the first video and audio channels of a USM file, to check keys against
+pub struct KeySample {
+    video: Vec<Vec<u8>>,
+    audio: Vec<Vec<u8>>,
+    hca_audio: bool,
+    audio_masked: bool
+}
+
+impl KeySample {
+    /// Check keys against the sample.
+    ///
+    /// The first VP9 frames must be well formed past the bytes left clear, and the first HCA blocks must decode
+    /// cleanly
+    pub fn verify(&self, key2: [u8; 4], key1: [u8; 4]) -> GICSResult<KeyCheck> {
+        let mask = USMMask::new(key2, key1);
+        let mut video: Vec<u8> = Vec::new();
+        for chunk in &self.video {
+            let start = video.len();
+            video.extend_from_slice(chunk);
+            mask.mask_video(&mut video[start..]);
+        }
+        let video_check = Self::verify_video(&video);
+        let mut audio: Vec<u8> = Vec::new();
+        for chunk in &self.audio {
+            let start = audio.len();
+            audio.extend_from_slice(chunk);
+            if self.audio_masked {
+                mask.mask_audio(&mut audio[start..]);
+            }
+        }
+        let audio_check = if self.hca_audio && !audio.is_empty() {
+            HCAFile::from_partial_reader(&mut Cursor::new(&audio), key2, key1)?.verify_key(KEY_CHECK_BLOCKS)?
+        } else {
+            KeyCheck::Inconclusive
+        };
+        Ok(video_check.and(audio_check))
+    }
+
+    /// Find which of `keys`, as `(key2, key1)`, the sample is encrypted with.
+    ///
+    /// The index of the first key checked as valid is returned, or else the index of the first key that could not be
+    /// checked, along with the outcome of its check
+    pub fn find_key(&self, keys: impl IntoIterator<Item = (u32, u32)>) -> GICSResult<Option<(usize, KeyCheck)>> {
+        let mut found: Option<(usize, KeyCheck)> = None;
+        for (index, (key2, key1)) in keys.into_iter().enumerate() {
+            match self.verify(key2.to_le_bytes(), key1.to_le_bytes())? {
+                KeyCheck::Valid => return Ok(Some((index, KeyCheck::Valid))),
+                KeyCheck::Inconclusive if found.is_none() => found = Some((index, KeyCheck::Inconclusive)),
+                _ => {}
+            }
+        }
+        Ok(found)
+    }
+
+    /// Check the VP9 frames of the start of an IVF stream
+    fn verify_video(stream: &[u8]) -> KeyCheck {
+        if stream.len() < 0x20 || &stream[0..4] != b"DKIF" || &stream[8..12] != b"VP90" {
+            return KeyCheck::Inconclusive;
+        }
+        let mut res = KeyCheck::Inconclusive;
+        let mut width: Option<u32> = None;
+        let mut offset = usize::from(u16::from_le_bytes([stream[6], stream[7]]));
+        while let Some(size) = stream.get(offset..offset + 4) {
+            let start = offset + 12;
+            let end = start + u32::from_le_bytes(size.try_into().unwrap_or_default()) as usize;
+            let Some(frame) = stream.get(start..end) else { break; };
+            match check_vp9_frame(frame, width) {
+                Some(Err(())) => return KeyCheck::Mismatch,
+                Some(Ok(layout)) => {
+                    width = Some(layout.width);
+                    // Marker bits alone are not enough to tell
+                    if layout.tile_count > 1 {
+                        res = KeyCheck::Valid;
+                    }
+                },
+                None => {}
+            }
+            offset = end;
+        }
+        res
+    }
+
+    /// Recover the key of the sample from known plaintext.
+    ///
+    /// Every byte of a masked video chunk is either left as is by the masks, whatever the key, or masked with a single
+    /// byte of `video_mask_1` : the uncompressed headers of the VP9 frames can be read without any key, and point at
+    /// the sizes of their tiles, whose highest byte is zero. When the audio is masked too, the sync word of every HCA
+    /// block, laid out by the clear header of the stream, gives away the even bytes of `video_mask_1`. Solved bytes
+    /// unmask more tile sizes, until the whole key is found, and checked against the sample
+    pub fn recover_key(&self) -> GICSResult<KeyRecovery> {
+        let (stream, positions) = self.unmasked_video();
+        let mut audio_hints: MaskHints = std::array::from_fn(|_| BTreeMap::new());
+        self.audio_hints(&mut audio_hints);
+        let mut solver = MaskSolver::new();
+        let hints = loop {
+            let mut hints = audio_hints.clone();
+            Self::video_hints(&stream, &positions, &solver.video_mask(), &mut hints);
+            if !solver.solve(&hints) {
+                break hints;
+            }
+        };
+
+        let candidates = solver.candidates(&hints);
+        if candidates.len() > KEY_RECOVERY_CANDIDATES {
+            return Ok(KeyRecovery::Partial(solver.video_mask()));
+        }
+        let mut found: Vec<([u8; 4], [u8; 4])> = Vec::new();
+        for key in candidates {
+            let (key2, key1) = ([key[4], key[5], key[6], 0], [key[0], key[1], key[2], key[3]]);
+            if self.verify(key2, key1)? == KeyCheck::Valid {
+                found.push((key2, key1));
+            }
+        }
+        // Bytes of the key the content never depends on cannot be told apart
+        match found[..] {
+            [(key2, key1)] => Ok(KeyRecovery::Found(u32::from_le_bytes(key2), u32::from_le_bytes(key1))),
+            _ => Ok(KeyRecovery::Partial(solver.video_mask()))
+        }
+    }
+
+    /// Video stream unmasked with a null `video_mask_1`, along with the byte of `video_mask_1` still masking every
+    /// byte of it, if any
+    fn unmasked_video(&self) -> (Vec<u8>, Vec<Option<u8>>) {
+        let null = USMMask::from_video_mask([0x00; 32]);
+        let full = USMMask::from_video_mask([0xFF; 32]);
+        let mut stream: Vec<u8> = Vec::new();
+        let mut positions: Vec<Option<u8>> = Vec::new();
+        for chunk in &self.video {
+            let mut unmasked = chunk.clone();
+            null.mask_video(&mut unmasked);
+            let mut inverted = chunk.clone();
+            full.mask_video(&mut inverted);
+            positions.extend(unmasked.iter().zip(&inverted).enumerate()
+                .map(|(offset, (a, b))| (a != b).then(|| ((offset - 0x40) & 0x1F) as u8)));
+            stream.append(&mut unmasked);
+        }
+        (stream, positions)
+    }
+
+    /// Vote for the bytes of `video_mask_1` masking the highest byte of every tile size that can be found
+    fn video_hints(stream: &[u8], positions: &[Option<u8>], mask: &[Option<u8>; 32], hints: &mut MaskHints) {
+        let byte = |offset: usize| -> Option<u8> {
+            let value = *stream.get(offset)?;
+            positions[offset].map_or(Some(value), |position| mask[usize::from(position)].map(|mask| value ^ mask))
+        };
+        let bytes = |offset: usize| -> Option<[u8; 4]> {
+            Some([byte(offset)?, byte(offset + 1)?, byte(offset + 2)?, byte(offset + 3)?])
+        };
+        if stream.len() < 0x20 || &stream[0..4] != b"DKIF" || &stream[8..12] != b"VP90" {
+            return;
+        }
+        let mut width: Option<u32> = None;
+        let mut offset = usize::from(u16::from_le_bytes([stream[6], stream[7]]));
+        while let Some(size) = bytes(offset) {
+            let start = offset + 12;
+            let end = start + u32::from_le_bytes(size) as usize;
+            if end > stream.len() {
+                break;
+            }
+            // Uncompressed headers fit in the bytes the masks leave as they are
+            let header: Vec<u8> = (start..end).take(0x100).map_while(byte).collect();
+            if let Some(Ok(layout)) = vp9_layout(&header, width) {
+                width = Some(layout.width);
+                let mut tile = start + layout.compressed_header + layout.header_size;
+                for _ in 1..layout.tile_count {
+                    if tile + 4 > end {
+                        break;
+                    }
+                    // Tiles are far smaller than 16 MiB
+                    if let (None, Some(position)) = (byte(tile), positions[tile]) {
+                        *hints[usize::from(position)].entry(stream[tile]).or_default() += 1;
+                    }
+                    let Some(size) = bytes(tile) else { break; };
+                    tile += 4 + u32::from_be_bytes(size) as usize;
+                }
+            }
+            offset = end;
+        }
+    }
+
+    /// Vote for the even bytes of `video_mask_1`, masking the sync words of HCA blocks in masked audio
+    fn audio_hints(&self, hints: &mut MaskHints) {
+        if !self.hca_audio || !self.audio_masked {
+            return;
+        }
+        let stream: Vec<u8> = self.audio.concat();
+        let Ok(hca) = HCAFile::from_partial_reader(&mut Cursor::new(&stream), [0; 4], [0; 4]) else { return; };
+        let data_offset = usize::from(hca.hca_header.data_offset);
+        let block_size = usize::from(hca.hca_header.block_size).max(1);
+        let mut start = 0;
+        for chunk in &self.audio {
+            // Odd bytes of the audio mask do not depend on the key
+            for (offset, &value) in chunk.iter().enumerate().skip(0x140).step_by(2) {
+                if start + offset >= data_offset && (start + offset - data_offset) % block_size < 2 {
+                    // The mask is the opposite of video_mask_1, and sync words are all ones
+                    *hints[(offset - 0x140) & 0x1F].entry(value).or_default() += 1;
+                }
+            }
+            start += chunk.len();
+        }
+    }
+}
+
+impl Demuxable for USMFile {
+    fn demux(self, video_extract: bool, audio_extract: bool, output: &Path) -> GICSResult<(Vec<PathBuf>, Vec<PathBuf>)> {
         // Base output folder
         let base_output = PathBuf::from(output);
         // Try and create it
@@ -116,81 +1137,203 @@ impl Demuxable for USMFile {
             std::fs::create_dir_all(base_output.as_path())?;
         }
 
-        // Base file name
-        let base_name = self.path.file_name().ok_or_else(|| GICSError::new("USM Path has no file name"))?;
-
-        // Video output
-        let mut video_path = base_output.clone();
-        video_path.push(base_name);
-        video_path.set_extension("ivf");
-        let mut video_output = BufWriter::new(File::create(video_path.as_path())?);
-
-        // Audio outputs
-        let audio_path = base_output.clone();
-        // The extension is set later
-        let audio_base_name: String = self.path
+        let metadata = self.metadata()?;
+        let base_name: String = self.path
             .file_stem().ok_or_else(|| GICSError::new("USM path has no file stem"))?
             .to_str().ok_or_else(|| GICSError::new("Unable to decode USM file stem to UTF-8"))?
             .into();
-        let mut audio_writers: HashMap<u8, BufWriter<_>> = HashMap::new();
+        let mut video_files: Vec<PathBuf> = Vec::new();
         let mut audio_files: Vec<PathBuf> = Vec::new();
 
-        while file_size > 0 {
-            // Read 32 bits at a time
-            let mut byte_block: [u8; 0x20] = [0; 0x20];
-            reader.read_exact(&mut byte_block)?;
-            file_size -= 32;
-
-            // Parse info from the content
-            info.sig = make_be32(&byte_block[0..4]);
-            info.data_size = make_be32(&byte_block[4..8]);
-            info.data_offset = byte_block[9];
-            info.padding_size = make_be16(&byte_block[10..12]);
-            info.chno = byte_block[12];
-            info.data_type = byte_block[15];
-            info.frame_time = make_be32(&byte_block[16..20]);
-            info.frame_rate = make_be32(&byte_block[20..24]);
-
-            // Now work with the rest of the data
-            // Read the size of the data
-            let size: usize = (info.data_size - u32::from(info.data_offset) - u32::from(info.padding_size)) as usize;
-            reader.seek(SeekFrom::Current(i64::from(info.data_offset - 0x18)))?;
-            let mut data = vec![0u8; size];
-            reader.read_exact(&mut data)?;
-            // Skip padding
-            reader.seek(SeekFrom::Current(i64::from(info.padding_size)))?;
-            // Account for it
-            file_size -= u64::from(info.data_size - 0x18);
+        self.demux_to(|stream| -> GICSResult<Option<BoxedSink>> {
+            // Audio outputs are named after their codec
+            let (extension, files) = match stream {
+                StreamId::Video(_) | StreamId::Alpha(_) if video_extract => ("ivf", &mut video_files),
+                StreamId::Audio(chno) if audio_extract => {
+                    (metadata.audio_info(chno).map_or("hca", AudioInfo::extension), &mut audio_files)
+                },
+                _ => return Ok(None)
+            };
+            let mut path = base_output.clone();
+            path.push(stream.file_name(&base_name, extension));
+            let sink = WriterSink::new(BufWriter::new(File::create(&path)?));
+            files.push(path);
+            Ok(Some(Box::new(sink)))
+        })?;
+        Ok((video_files, audio_files))
+    }
+
+    fn demux_to<'a, F>(self, mut sinks: F) -> GICSResult<()>
+        where F: FnMut(StreamId) -> GICSResult<Option<BoxedSink<'a>>>
+    {
+        // Streams we do not want are kept as `None` so that we only ask once, and sinks are finished in the order their
+        // streams first appear
+        let mut outputs: Vec<(StreamId, Option<BoxedSink<'a>>)> = Vec::new();
 
+        for chunk in self.chunks()? {
+            let chunk = chunk?;
+            if !chunk.is_stream_data() {
+                continue;
+            }
             // Depending on the signature, do something different
-            match info.sig {
-                //0x4352_4944 => { /* (CRID) Nothing to do */ },
-                0x4053_4656 => {
-                    // It's a video block (@SFV)
-                   if info.data_type == 0 && video_extract {
-                        self.mask_video(&mut data, size as usize);
-                        video_output.write_all(&data)?;
-                    }
-                },
-                0x4053_4641 => {
-                    if info.data_type == 0  && audio_extract {
-                        // It's an audio block (@SFA)
-                        // FIXME: Do we extract audio ?
-                        if !audio_writers.contains_key(&info.chno) {
-                        }
-                        if let std::collections::hash_map::Entry::Vacant(e) = audio_writers.entry(info.chno) {
-                            let filename = format!("{}_{}.hca", audio_base_name, info.chno);
-                            let mut path = audio_path.clone();
-                            path.push(filename);
-                            audio_files.push(path.clone());
-                            e.insert(BufWriter::new(File::create(path)?));
-                        }
-                        audio_writers.get_mut(&info.chno).unwrap().write_all(&data)?;
-                    }
-                }
-                _ => { /* we don't care */}
+            let stream = match chunk.kind {
+                ChunkKind::Video => StreamId::Video(chunk.chno),
+                ChunkKind::Audio => StreamId::Audio(chunk.chno),
+                ChunkKind::Alpha => StreamId::Alpha(chunk.chno),
+                ChunkKind::Subtitle => StreamId::Subtitle(chunk.chno),
+                _ => { /* we don't care */ continue; }
+            };
+            let index = if let Some(index) = outputs.iter().position(|(id, _)| *id == stream) {
+                index
+            } else {
+                outputs.push((stream, sinks(stream)?));
+                outputs.len() - 1
+            };
+            if let Some(sink) = outputs[index].1.as_mut() {
+                sink.write_chunk(&chunk)?;
             }
         }
-        Ok((video_path, audio_files))
+
+        outputs.iter_mut()
+            .filter_map(|(_, sink)| sink.as_mut())
+            .try_for_each(|sink| sink.finish())
+    }
+}
+
+#[cfg(test)]
+mod usm_tests {
+    use super::*;
+    use super::hca_tests::{chunk, encoded, encrypted, seal_header, KEY1, KEY2};
+
+    /// Sample of a file whose only stream is the HCA audio `hca`
+    fn hca_sample(hca: &[u8]) -> KeySample {
+        KeySample {
+            video: Vec::new(),
+            audio: hca.chunks(0x400).map(<[u8]>::to_vec).collect(),
+            hca_audio: true,
+            audio_masked: false
+        }
+    }
+
+    /// Keys close to the test key, but wrong
+    fn wrong_keys() -> Vec<(u32, u32)> {
+        (1..=8_u32).map(|n| (KEY2 ^ n.wrapping_mul(0x9E37_79B9), KEY1.rotate_left(n))).collect()
+    }
+
+    #[test]
+    fn find_key_picks_the_right_candidate() {
+        let sample = hca_sample(&encoded(encrypted(), 2, 0x4000));
+        let mut keys = wrong_keys();
+        keys.insert(5, (KEY2, KEY1));
+        assert_eq!(sample.find_key(keys).unwrap(), Some((5, KeyCheck::Valid)));
+        assert_eq!(sample.find_key(wrong_keys()).unwrap(), None);
+    }
+
+    #[test]
+    fn find_key_rejects_wrong_candidates_with_high_frequency_bands() {
+        let mut hca = encoded(HCAEncoderOptions { quality: HCAQuality::Lowest, ..encrypted() }, 2, 0x4000);
+        let comp = chunk(&hca, *b"comp");
+        hca[comp + 0xA] = 0x80;
+        hca[comp + 0xD] = 4;
+        seal_header(&mut hca);
+        assert_ne!(hca_sample(&hca).find_key(wrong_keys()).unwrap().map(|(_, check)| check), Some(KeyCheck::Valid));
+    }
+
+    /// Mask `chunk` the way `mask` unmasks it
+    fn mask_video(mask: &USMMask, chunk: &mut [u8]) {
+        let plain = chunk.to_vec();
+        let mut rolling: [u8; 0x20] = mask.video_mask_2;
+        for i in 0x140..chunk.len() {
+            chunk[i] ^= rolling[i & 0x1F];
+            rolling[i & 0x1F] = plain[i] ^ mask.video_mask_2[i & 0x1F];
+        }
+        let mut rolling: [u8; 0x20] = mask.video_mask_1;
+        for i in 0..0x100 {
+            rolling[i & 0x1F] ^= plain[0x140 + i];
+            chunk[0x40 + i] ^= rolling[i & 0x1F];
+        }
+    }
+
+    /// Chunk of an IVF stream holding one VP9 key frame of 4 tiles, the first tile size of which is at `0x140 + position`,
+    /// where only `video_mask_1[position]` masks its highest byte
+    fn vp9_chunk(position: usize, first: bool) -> Vec<u8> {
+        let mut chunk: Vec<u8> = Vec::new();
+        if first {
+            chunk.extend_from_slice(b"DKIF\0\0\x20\0VP90");
+            chunk.resize(0x20, 0);
+        }
+        let frame_start = chunk.len() + 12;
+        let frame_size = 0x300 - frame_start;
+        chunk.extend_from_slice(&(frame_size as u32).to_le_bytes());
+        chunk.resize(frame_start, 0);
+
+        // Key frame of profile 0, 64 pixels wide, with 4 tile rows
+        let mut header = BitWriter { data: Vec::new(), bit: 0 };
+        for (value, bits) in [(0x82, 8), (0x0049_8342, 24), (0, 4), (63, 16), (63, 16), (0, 1), (0, 4), (0, 10), (0, 8),
+            (0, 4), (3, 2)] {
+            header.write(value, bits);
+        }
+        let tile = 0x140 + position;
+        let compressed_header = frame_start + (header.bit + 16).div_ceil(8);
+        header.write((tile - compressed_header) as u32, 16);
+        chunk.extend_from_slice(&header.data);
+        chunk.resize(tile, 0);
+        for _ in 0..3 {
+            chunk.extend_from_slice(&0x20_u32.to_be_bytes());
+            chunk.resize(chunk.len() + 0x20, 0);
+        }
+        chunk.resize(0x300, 0);
+        chunk
+    }
+
+    #[test]
+    fn derived_masks() {
+        let mask = USMMask::new(KEY2.to_le_bytes(), KEY1.to_le_bytes());
+        assert_eq!(mask.video_mask_1[..4], [0x45, 0x23, 0xA1, 0xCC]);
+        for i in 0..0x20 {
+            assert_eq!(mask.video_mask_2[i], !mask.video_mask_1[i]);
+            assert_eq!(mask.audio_mask[i], if i % 2 == 1 { b"URUC"[i >> 1 & 3] } else { !mask.video_mask_1[i] });
+        }
+        assert_eq!(USMMask::from_video_mask(mask.video_mask_1).audio_mask, mask.audio_mask);
+    }
+
+    #[test]
+    fn mask_dependencies() {
+        let solver = MaskSolver::new();
+        for byte in 0..7 {
+            assert_eq!(solver.dependencies[byte], 1 << byte);
+        }
+        // video_mask_1[8] is the sum of the bytes 1 and 2 of key1
+        assert_eq!(solver.dependencies[8], 0b110);
+    }
+
+    #[test]
+    fn recover_key_from_masked_audio() {
+        let mask = USMMask::new(KEY2.to_le_bytes(), KEY1.to_le_bytes());
+        // Blocks are 0x220 bytes long : chunks of 0x402 bytes shift their sync words onto every even byte of the mask
+        let audio = encoded(HCAEncoderOptions::default(), 2, 0x10000);
+        let mut audio: Vec<Vec<u8>> = audio.chunks(0x402).map(<[u8]>::to_vec).collect();
+        for chunk in &mut audio {
+            mask.mask_audio(chunk);
+        }
+        let sample = KeySample { video: Vec::new(), audio, hca_audio: true, audio_masked: true };
+        assert_eq!(sample.recover_key().unwrap(), KeyRecovery::Found(KEY2 & 0x00FF_FFFF, KEY1));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn recover_key_from_masked_video() {
+        let mask = USMMask::new(KEY2.to_le_bytes(), KEY1.to_le_bytes());
+        let mut video: Vec<Vec<u8>> = (0..7).map(|position| vp9_chunk(position, position == 0)).collect();
+        assert_eq!(KeySample::verify_video(&video.concat()), KeyCheck::Valid);
+        for chunk in &mut video {
+            mask_video(&mask, chunk);
+        }
+        let sample = KeySample { video, audio: Vec::new(), hca_audio: false, audio_masked: false };
+        assert_eq!(sample.recover_key().unwrap(), KeyRecovery::Found(KEY2 & 0x00FF_FFFF, KEY1));
+
+        // Too many keys are left to try with only the frames masked by the first 3 bytes of the key
+        let partial = KeySample { video: sample.video[..3].to_vec(), ..sample };
+        let KeyRecovery::Partial(solved) = partial.recover_key().unwrap() else { panic!("key recovered from 3 frames") };
+        assert_eq!(solved[..4], [Some(mask.video_mask_1[0]), Some(mask.video_mask_1[1]), Some(mask.video_mask_1[2]), None]);
+    }
+}
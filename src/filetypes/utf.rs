@@ -0,0 +1,376 @@
+/// A value stored in a cell of a CRI @UTF table
+#[derive(Clone, Debug, PartialEq)]
+pub enum UTFValue {
+    /// Column declared without any value
+    None,
+    /// Unsigned 8 bits integer
+    U8(u8),
+    /// Signed 8 bits integer
+    I8(i8),
+    /// Unsigned 16 bits integer
+    U16(u16),
+    /// Signed 16 bits integer
+    I16(i16),
+    /// Unsigned 32 bits integer
+    U32(u32),
+    /// Signed 32 bits integer
+    I32(i32),
+    /// Unsigned 64 bits integer
+    U64(u64),
+    /// Signed 64 bits integer
+    I64(i64),
+    /// Single precision float
+    F32(f32),
+    /// Double precision float
+    F64(f64),
+    /// String, from the string table
+    String(String),
+    /// Raw binary data, from the data area (also used for 128 bits integers)
+    Data(Vec<u8>)
+}
+
+impl UTFValue {
+    /// Value as an unsigned integer, if it is an integer that fits
+    #[must_use]
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Self::U8(v) => Some(u64::from(v)),
+            Self::U16(v) => Some(u64::from(v)),
+            Self::U32(v) => Some(u64::from(v)),
+            Self::U64(v) => Some(v),
+            Self::I8(v) => v.try_into().ok(),
+            Self::I16(v) => v.try_into().ok(),
+            Self::I32(v) => v.try_into().ok(),
+            Self::I64(v) => v.try_into().ok(),
+            _ => None
+        }
+    }
+
+    /// Value as a float, if it is a number
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::F32(v) => Some(f64::from(v)),
+            Self::F64(v) => Some(v),
+            Self::I8(v) => Some(f64::from(v)),
+            Self::I16(v) => Some(f64::from(v)),
+            Self::I32(v) => Some(f64::from(v)),
+            Self::I64(v) => Some(v as f64),
+            _ => self.as_u64().map(|v| v as f64)
+        }
+    }
+
+    /// Value as a string, if it is one
+    #[must_use]
+    pub const fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s.as_str()),
+            _ => None
+        }
+    }
+}
+
+/// A CRI @UTF table, the key/value format used by CRI for every kind of metadata
+#[derive(Clone, Debug)]
+pub struct UTFTable {
+    name: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<UTFValue>>
+}
+
+// Column storage flags, in the upper nibble of the column type
+const UTF_COLUMN_NAME: u8 = 0x10;
+const UTF_COLUMN_DEFAULT: u8 = 0x20;
+const UTF_COLUMN_ROW: u8 = 0x40;
+
+impl UTFTable {
+    /// Parse a @UTF table, starting with its `@UTF` signature
+    pub fn parse(data: &[u8]) -> GICSResult<Self> {
+        if data.len() < 0x20 || &data[0..4] != b"@UTF" {
+            return Err(GICSError::new("Not a @UTF table"));
+        }
+        let table_size = make_be32(&data[4..8]) as usize;
+        // Every offset is relative to the end of the size field
+        let table: &[u8] = data.get(8..8 + table_size)
+            .ok_or_else(|| GICSError::new("Truncated @UTF table"))?;
+        if table.len() < 0x18 {
+            return Err(GICSError::new("Truncated @UTF table header"));
+        }
+
+        let rows_offset = usize::from(make_be16(&table[0x02..0x04]));
+        let strings_offset = make_be32(&table[0x04..0x08]) as usize;
+        let data_offset = make_be32(&table[0x08..0x0C]) as usize;
+        let name_offset = make_be32(&table[0x0C..0x10]) as usize;
+        let column_count = make_be16(&table[0x10..0x12]);
+        let row_width = usize::from(make_be16(&table[0x12..0x14]));
+        let row_count = make_be32(&table[0x14..0x18]) as usize;
+
+        let strings: &[u8] = table.get(strings_offset..)
+            .ok_or_else(|| GICSError::new("@UTF string table out of bounds"))?;
+        let blobs: &[u8] = table.get(data_offset..).unwrap_or(&[]);
+
+        // Read the schema : every column has a type, a name and maybe a constant value
+        let mut schema: Vec<(u8, Option<UTFValue>)> = Vec::new();
+        let mut columns: Vec<String> = Vec::new();
+        let mut offset: usize = 0x18;
+        for _ in 0..column_count {
+            let flags = *table.get(offset).ok_or_else(|| GICSError::new("@UTF schema out of bounds"))?;
+            offset += 1;
+            let name = if flags & UTF_COLUMN_NAME == 0 {
+                String::new()
+            } else {
+                let name = Self::read_string(strings, Self::read_u32(table, offset)? as usize)?;
+                offset += 4;
+                name
+            };
+            let constant = if flags & UTF_COLUMN_DEFAULT == 0 {
+                None
+            } else {
+                let (value, size) = Self::read_value(table, offset, flags & 0xF, strings, blobs)?;
+                offset += size;
+                Some(value)
+            };
+            schema.push((flags, constant));
+            columns.push(name);
+        }
+
+        // Read the rows, which must fit in the table (rows of constants alone still take a byte each, as a bound)
+        if row_count.checked_mul(row_width.max(1)).and_then(|size| size.checked_add(rows_offset)).is_none_or(|end| end > table.len()) {
+            return Err(GICSError::new("@UTF rows out of bounds"));
+        }
+        let mut rows: Vec<Vec<UTFValue>> = Vec::with_capacity(row_count);
+        for row in 0..row_count {
+            let mut offset = rows_offset + row * row_width;
+            let mut values: Vec<UTFValue> = Vec::with_capacity(schema.len());
+            for (flags, constant) in &schema {
+                let value = if let Some(constant) = constant {
+                    constant.clone()
+                } else if flags & UTF_COLUMN_ROW == 0 {
+                    UTFValue::None
+                } else {
+                    let (value, size) = Self::read_value(table, offset, flags & 0xF, strings, blobs)?;
+                    offset += size;
+                    value
+                };
+                values.push(value);
+            }
+            rows.push(values);
+        }
+
+        Ok(Self {
+            name: Self::read_string(strings, name_offset)?,
+            columns,
+            rows
+        })
+    }
+
+    fn read_u32(table: &[u8], offset: usize) -> GICSResult<u32> {
+        table.get(offset..offset + 4)
+            .map(make_be32)
+            .ok_or_else(|| GICSError::new("@UTF value out of bounds"))
+    }
+
+    fn read_string(strings: &[u8], offset: usize) -> GICSResult<String> {
+        let start: &[u8] = strings.get(offset..)
+            .ok_or_else(|| GICSError::new("@UTF string out of bounds"))?;
+        let end = start.iter().position(|&c| c == 0).unwrap_or(start.len());
+        Ok(String::from_utf8_lossy(&start[..end]).into_owned())
+    }
+
+    /// Read a value of the given type, returning it along with the number of bytes it used
+    fn read_value(table: &[u8], offset: usize, kind: u8, strings: &[u8], blobs: &[u8]) -> GICSResult<(UTFValue, usize)> {
+        let size: usize = match kind {
+            0x0 | 0x1 => 1,
+            0x2 | 0x3 => 2,
+            0x4 | 0x5 | 0x8 | 0xA => 4,
+            0x6 | 0x7 | 0x9 | 0xB => 8,
+            0xC => 16,
+            _ => return Err(GICSError::new(&format!("Unknown @UTF column type {kind:#X}")))
+        };
+        let bytes: &[u8] = table.get(offset..offset + size)
+            .ok_or_else(|| GICSError::new("@UTF value out of bounds"))?;
+        let value = match kind {
+            0x0 => UTFValue::U8(bytes[0]),
+            0x1 => UTFValue::I8(i8::from_be_bytes([bytes[0]])),
+            0x2 => UTFValue::U16(make_be16(bytes)),
+            0x3 => UTFValue::I16(i16::from_be_bytes([bytes[0], bytes[1]])),
+            0x4 => UTFValue::U32(make_be32(bytes)),
+            0x5 => UTFValue::I32(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            0x6 => UTFValue::U64(u64::from_be_bytes(bytes.try_into().unwrap_or_default())),
+            0x7 => UTFValue::I64(i64::from_be_bytes(bytes.try_into().unwrap_or_default())),
+            0x8 => UTFValue::F32(f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            0x9 => UTFValue::F64(f64::from_be_bytes(bytes.try_into().unwrap_or_default())),
+            0xA => UTFValue::String(Self::read_string(strings, make_be32(bytes) as usize)?),
+            0xB => {
+                let start = make_be32(&bytes[0..4]) as usize;
+                let length = make_be32(&bytes[4..8]) as usize;
+                UTFValue::Data(blobs.get(start..start + length)
+                    .ok_or_else(|| GICSError::new("@UTF data out of bounds"))?
+                    .to_vec())
+            },
+            _ => UTFValue::Data(bytes.to_vec())
+        };
+        Ok((value, size))
+    }
+
+    /// Name of the table
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Names of the columns of the table
+    #[must_use]
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Number of rows of the table
+    #[must_use]
+    pub const fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Value of the cell at `row` in the column named `column`
+    #[must_use]
+    pub fn get(&self, row: usize, column: &str) -> Option<&UTFValue> {
+        let index = self.columns.iter().position(|c| c == column)?;
+        self.rows.get(row).and_then(|r| r.get(index))
+    }
+
+    /// Value of the cell at `row` in the column named `column`, as an unsigned integer
+    #[must_use]
+    pub fn get_u64(&self, row: usize, column: &str) -> Option<u64> {
+        self.get(row, column).and_then(UTFValue::as_u64)
+    }
+
+    /// Value of the cell at `row` in the column named `column`, as a string
+    #[must_use]
+    pub fn get_str(&self, row: usize, column: &str) -> Option<&str> {
+        self.get(row, column).and_then(UTFValue::as_str)
+    }
+}
+
+#[cfg(test)]
+mod utf_tests {
+    use super::*;
+
+    /// Parts of a @UTF table being built
+    #[derive(Default)]
+    struct TableBuilder {
+        schema: Vec<u8>,
+        rows: Vec<u8>,
+        strings: Vec<u8>,
+        blobs: Vec<u8>
+    }
+
+    impl TableBuilder {
+        fn string(&mut self, string: &str) -> [u8; 4] {
+            let offset = self.strings.len() as u32;
+            self.strings.extend(string.as_bytes());
+            self.strings.push(0);
+            offset.to_be_bytes()
+        }
+
+        fn column(&mut self, flags: u8, name: &str) {
+            let name = self.string(name);
+            self.schema.push(flags);
+            self.schema.extend(name);
+        }
+
+        /// The whole table, named `name`, with `row_count` rows `row_width` bytes wide
+        fn build(mut self, name: &str, column_count: u16, row_width: u16, row_count: u32) -> Vec<u8> {
+            let name = self.string(name);
+            let rows_offset = 0x18 + self.schema.len();
+            let strings_offset = rows_offset + self.rows.len();
+            let data_offset = strings_offset + self.strings.len();
+            let mut table: Vec<u8> = vec![0, 1];
+            table.extend((rows_offset as u16).to_be_bytes());
+            table.extend((strings_offset as u32).to_be_bytes());
+            table.extend((data_offset as u32).to_be_bytes());
+            table.extend(name);
+            table.extend(column_count.to_be_bytes());
+            table.extend(row_width.to_be_bytes());
+            table.extend(row_count.to_be_bytes());
+            for part in [self.schema, self.rows, self.strings, self.blobs] {
+                table.extend(part);
+            }
+            let mut res: Vec<u8> = b"@UTF".to_vec();
+            res.extend((table.len() as u32).to_be_bytes());
+            res.extend(table);
+            res
+        }
+    }
+
+    /// Table of 2 rows with a constant string, a zero-storage column, then a 32 bits integer and data in every row
+    fn sample_table() -> Vec<u8> {
+        let mut builder = TableBuilder::default();
+        builder.column(UTF_COLUMN_NAME | UTF_COLUMN_DEFAULT | 0xA, "codec");
+        let constant = builder.string("vp9");
+        builder.schema.extend(constant);
+        builder.column(UTF_COLUMN_NAME | 0x4, "unused");
+        builder.column(UTF_COLUMN_NAME | UTF_COLUMN_ROW | 0x4, "width");
+        builder.column(UTF_COLUMN_NAME | UTF_COLUMN_ROW | 0xB, "extra");
+        builder.blobs.extend(b"abcdef");
+        for (width, start, length) in [(1920_u32, 0_u32, 2_u32), (1080, 2, 4)] {
+            builder.rows.extend(width.to_be_bytes());
+            builder.rows.extend(start.to_be_bytes());
+            builder.rows.extend(length.to_be_bytes());
+        }
+        builder.build("VIDEO_HDRINFO", 4, 12, 2)
+    }
+
+    #[test]
+    fn parse_columns() {
+        let table = UTFTable::parse(&sample_table()).unwrap();
+        assert_eq!(table.name(), "VIDEO_HDRINFO");
+        assert_eq!(table.columns(), ["codec", "unused", "width", "extra"]);
+        assert_eq!(table.row_count(), 2);
+        for row in 0..2 {
+            assert_eq!(table.get_str(row, "codec"), Some("vp9"));
+            assert_eq!(table.get(row, "unused"), Some(&UTFValue::None));
+        }
+        assert_eq!(table.get_u64(0, "width"), Some(1920));
+        assert_eq!(table.get_u64(1, "width"), Some(1080));
+        assert_eq!(table.get(0, "extra"), Some(&UTFValue::Data(b"ab".to_vec())));
+        assert_eq!(table.get(1, "extra"), Some(&UTFValue::Data(b"cdef".to_vec())));
+        assert_eq!(table.get(2, "width"), None);
+        assert_eq!(table.get(0, "height"), None);
+    }
+
+    #[test]
+    fn truncated_tables() {
+        let data = sample_table();
+        for size in 0..data.len() {
+            assert!(UTFTable::parse(&data[..size]).is_err(), "table cut at {size:#X}");
+        }
+        // The size of the table is checked, not the size of the data holding it
+        let mut shrunk = data;
+        shrunk[4..8].copy_from_slice(&0x20_u32.to_be_bytes());
+        assert!(UTFTable::parse(&shrunk).is_err());
+    }
+
+    #[test]
+    fn out_of_bounds_tables() {
+        let data = sample_table();
+        let corrupt = |offset: usize, bytes: &[u8]| {
+            let mut corrupt = data.clone();
+            corrupt[offset..offset + bytes.len()].copy_from_slice(bytes);
+            UTFTable::parse(&corrupt)
+        };
+        // Rows, strings and name
+        assert!(corrupt(0xA, &0xFFFF_u16.to_be_bytes()).is_err());
+        assert!(corrupt(0xC, &0xFFFF_FFFF_u32.to_be_bytes()).is_err());
+        assert!(corrupt(0x14, &0xFFFF_FFFF_u32.to_be_bytes()).is_err());
+        // Row count and width
+        assert!(corrupt(0x1C, &0xFFFF_FFFF_u32.to_be_bytes()).is_err());
+        assert!(corrupt(0x1A, &0xFFFF_u16.to_be_bytes()).is_err());
+        // Column count, then the type of the first column
+        assert!(corrupt(0x18, &0xFFFF_u16.to_be_bytes()).is_err());
+        assert!(corrupt(0x20, &[UTF_COLUMN_NAME | UTF_COLUMN_DEFAULT | 0xF]).is_err());
+        // Data of the last row
+        let last_row = data.windows(12).position(|row| row == [0, 0, 4, 0x38, 0, 0, 0, 2, 0, 0, 0, 4]).unwrap();
+        assert!(corrupt(last_row + 8, &0xFFFF_FFFF_u32.to_be_bytes()).is_err());
+    }
+}
@@ -12,7 +12,9 @@ struct WaveRiff {
     fmt_sampling_rate: u32,
     fmt_sampling_per_sec: u32,
     fmt_sampling_size: u16,
-    fmt_bit_count: u16
+    fmt_bit_count: u16,
+    /// Speakers of the channels, written in a `WAVE_FORMAT_EXTENSIBLE` header when set
+    fmt_channel_mask: Option<u32>
 }
 
 impl Default for WaveRiff {
@@ -28,12 +30,50 @@ impl Default for WaveRiff {
             fmt_sampling_rate: 0,
             fmt_sampling_per_sec: 0,
             fmt_sampling_size: 0,
-            fmt_bit_count: 0
+            fmt_bit_count: 0,
+            fmt_channel_mask: None
         }
     }
 }
 
 impl WaveRiff {
+    /// Header of a WAV file holding `format` samples
+    fn for_format(format: PCMFormat, channel_count: u16, sampling_rate: u32) -> Self {
+        let mut res = Self::default();
+        res.fmt_type = if format == PCMFormat::Float { 3 } else { 1 };
+        res.fmt_channel_count = channel_count;
+        res.fmt_bit_count = format.bits();
+        res.fmt_sampling_rate = sampling_rate;
+        res.fmt_sampling_size = format.bits() / 8 * channel_count;
+        res.fmt_sampling_per_sec = sampling_rate * u32::from(res.fmt_sampling_size);
+        // Players only know which speaker plays which channel beyond stereo from an extensible header
+        if channel_count > 2 {
+            res.fmt_size = 0x28;
+            res.fmt_channel_mask = Some(Self::channel_mask(channel_count));
+        }
+        res
+    }
+
+    /// Speakers of the usual layouts of `channel_count` channels, as a `WAVE_FORMAT_EXTENSIBLE` channel mask
+    const fn channel_mask(channel_count: u16) -> u32 {
+        match channel_count {
+            1 => 0x4,   // FC
+            2 => 0x3,   // FL FR
+            3 => 0x7,   // FL FR FC
+            4 => 0x33,  // FL FR BL BR
+            5 => 0x37,  // FL FR FC BL BR
+            6 => 0x3F,  // FL FR FC LFE BL BR
+            7 => 0x13F, // FL FR FC LFE BL BR BC
+            8 => 0x63F, // FL FR FC LFE BL BR SL SR
+            _ => 0      // No particular layout
+        }
+    }
+
+    /// Size of the form type and `fmt ` chunk, counted in the RIFF size
+    const fn header_size(&self) -> u32 {
+        0xC + self.fmt_size
+    }
+
     fn build_byte_array(&self) -> Vec<u8> {
         let mut result = Vec::new();
         result.extend(self.riff);
@@ -41,18 +81,85 @@ impl WaveRiff {
         result.extend(self.wave);
         result.extend(self.fmt);
         result.extend(self.fmt_size.to_le_bytes());
-        result.extend(self.fmt_type.to_le_bytes());
+        result.extend(self.fmt_channel_mask.map_or(self.fmt_type, |_| 0xFFFE).to_le_bytes());
         result.extend(self.fmt_channel_count.to_le_bytes());
         result.extend(self.fmt_sampling_rate.to_le_bytes());
         result.extend(self.fmt_sampling_per_sec.to_le_bytes());
         result.extend(self.fmt_sampling_size.to_le_bytes());
         result.extend(self.fmt_bit_count.to_le_bytes());
+        if let Some(channel_mask) = self.fmt_channel_mask {
+            // Extension size, valid bits, channel mask, then the actual format at the start of its sub-format GUID
+            result.extend(22_u16.to_le_bytes());
+            result.extend(self.fmt_bit_count.to_le_bytes());
+            result.extend(channel_mask.to_le_bytes());
+            result.extend(self.fmt_type.to_le_bytes());
+            result.extend([0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71]);
+        }
 
         result
     }
 }
 
-#[allow(dead_code)]
+/// Sample format of the WAV files written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PCMFormat {
+    /// Unsigned 8-bit integers
+    Int8,
+    /// Signed 16-bit integers
+    #[default]
+    Int16,
+    /// Signed 24-bit integers
+    Int24,
+    /// Signed 32-bit integers
+    Int32,
+    /// 32-bit IEEE floats
+    Float
+}
+
+impl PCMFormat {
+    /// Size of a sample, in bits
+    #[must_use]
+    pub const fn bits(self) -> u16 {
+        match self {
+            Self::Int8 => 8,
+            Self::Int16 => 16,
+            Self::Int24 => 24,
+            Self::Int32 | Self::Float => 32
+        }
+    }
+
+    /// Append a sample, nominally between -1 and 1, to `pcm`. Integer samples saturate, floats are written as they are
+    fn push_sample(self, sample: f64, pcm: &mut Vec<u8>) {
+        let clamped = sample.clamp(-1.0, 1.0);
+        match self {
+            Self::Int8 => pcm.push(((clamped * f64::from(i8::MAX)).trunc() + 128.0) as u8),
+            Self::Int16 => pcm.extend_from_slice(&((clamped * f64::from(i16::MAX)).trunc() as i16).to_le_bytes()),
+            Self::Int24 => pcm.extend_from_slice(&((clamped * f64::from(0x007F_FFFF)).trunc() as i32).to_le_bytes()[0..3]),
+            Self::Int32 => pcm.extend_from_slice(&((clamped * f64::from(i32::MAX)).trunc() as i32).to_le_bytes()),
+            Self::Float => pcm.extend_from_slice(&(sample as f32).to_le_bytes())
+        }
+    }
+}
+
+/// Sample format and gain of the WAV files written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PCMOptions {
+    /// Format of the samples
+    pub format: PCMFormat,
+    /// Linear gain applied to every sample
+    pub gain: f32
+}
+
+impl Default for PCMOptions {
+    fn default() -> Self {
+        Self {
+            format: PCMFormat::default(),
+            gain: 1.0
+        }
+    }
+}
+
+/// `smpl` chunk of a WAV file, holding a single loop
 struct WaveSample {
     smpl: [u8; 4],
     smpl_size: u32,
@@ -97,6 +204,35 @@ impl Default for WaveSample {
     }
 }
 
+impl WaveSample {
+    /// Chunk of a file looping forever from sample `start` until right before sample `end`
+    fn for_loop(sampling_rate: u32, start: u32, end: u32) -> Self {
+        Self {
+            sample_period: 1_000_000_000 / sampling_rate.max(1),
+            midi_unity_note: 60,
+            sampler_data: 0,
+            loop_start: start,
+            // The end of the loop is its last sample
+            loop_end: end - 1,
+            ..Self::default()
+        }
+    }
+
+    fn build_byte_array(&self) -> Vec<u8> {
+        let mut res = Vec::new();
+        res.extend(self.smpl);
+        for field in [
+            self.smpl_size, self.manufacturer, self.product, self.sample_period, self.midi_unity_note,
+            self.midi_pitch_fraction, self.smpte_format, self.smpte_offset, self.sample_loops, self.sampler_data,
+            self.loop_identifier, self.loop_type, self.loop_start, self.loop_end, self.loop_fraction, self.loop_play_count
+        ] {
+            res.extend(field.to_le_bytes());
+        }
+        res
+    }
+}
+
+/// `data` chunk header of a WAV file
 pub struct WaveData {
     data: [u8; 4],
     data_size: u32
@@ -120,7 +256,155 @@ impl WaveData {
         res
     }
 
-    fn set_data_size(&mut self, dsize: u32) {
+    const fn set_data_size(&mut self, dsize: u32) {
         self.data_size = dsize;
     }
-}
\ No newline at end of file
+}
+
+/// A PCM WAV file, with the layout of its samples, read block by block
+struct WaveReader<R: Read + Seek> {
+    fmt_type: u16,
+    channel_count: u16,
+    sampling_rate: u32,
+    bit_count: u16,
+    data_start: u64,
+    data_size: u64,
+    reader: R
+}
+
+impl<R: Read + Seek> WaveReader<R> {
+    fn new(mut reader: R) -> GICSResult<Self> {
+        let mut riff: [u8; 12] = [0; 12];
+        reader.read_exact(&mut riff)?;
+        if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+            return Err(GICSError::new("Not a WAV file"));
+        }
+        let mut fmt: Option<Vec<u8>> = None;
+        let declared_size = loop {
+            let mut chunk: [u8; 8] = [0; 8];
+            reader.read_exact(&mut chunk)?;
+            let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+            match &chunk[0..4] {
+                b"data" => break u64::from(size),
+                b"fmt " => {
+                    let mut data = vec![0u8; size as usize];
+                    reader.read_exact(&mut data)?;
+                    fmt = Some(data);
+                    // Chunks are aligned on two bytes
+                    reader.seek(SeekFrom::Current(i64::from(size & 1)))?;
+                },
+                _ => { reader.seek(SeekFrom::Current(i64::from(size + (size & 1))))?; }
+            }
+        };
+        let fmt = fmt.filter(|f| f.len() >= 0x10).ok_or_else(|| GICSError::new("WAV file has no valid fmt chunk"))?;
+        let data_start = reader.stream_position()?;
+        // Streamed files do not always know the size of their data
+        let data_size = match reader.seek(SeekFrom::End(0))? - data_start {
+            available if declared_size == 0 || declared_size > available => available,
+            _ => declared_size
+        };
+        reader.seek(SeekFrom::Start(data_start))?;
+
+        let read16 = |offset: usize| u16::from_le_bytes([fmt[offset], fmt[offset + 1]]);
+        // WAVE_FORMAT_EXTENSIBLE keeps the actual format at the start of its sub-format GUID
+        let fmt_type = if read16(0) == 0xFFFE && fmt.len() >= 0x1A { read16(0x18) } else { read16(0) };
+        Ok(Self {
+            fmt_type,
+            channel_count: read16(2),
+            sampling_rate: u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]),
+            bit_count: read16(0x0E),
+            data_start,
+            data_size,
+            reader
+        })
+    }
+
+    /// Size of one sample, for every channel
+    fn frame_size(&self) -> u64 {
+        u64::from(self.bit_count / 8) * u64::from(self.channel_count)
+    }
+
+    /// Duration of the samples, in milliseconds
+    fn duration(&self) -> u64 {
+        let frames = self.data_size / self.frame_size().max(1);
+        frames * 1000 / u64::from(self.sampling_rate.max(1))
+    }
+
+    /// Read up to `frames` samples of every channel, starting with sample `start`
+    fn read_frames(&mut self, start: u64, frames: u64) -> GICSResult<Vec<u8>> {
+        let offset = start * self.frame_size();
+        let size = (frames * self.frame_size()).min(self.data_size.saturating_sub(offset));
+        self.reader.seek(SeekFrom::Start(self.data_start + offset))?;
+        let mut data = vec![0u8; size as usize];
+        self.reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    /// Number of samples of every channel
+    fn frame_count(&self) -> u64 {
+        self.data_size / self.frame_size().max(1)
+    }
+
+    /// Read up to `frames` samples of every channel, starting with sample `start`, interleaved and scaled between -1 and 1
+    fn read_samples(&mut self, start: u64, frames: u64) -> GICSResult<Vec<f32>> {
+        let data = self.read_frames(start, frames)?;
+        let samples = match (self.fmt_type, self.bit_count) {
+            (1, 8) => data.iter().map(|&b| (f32::from(b) - 128.0) / 128.0).collect(),
+            (1, 16) => data.chunks_exact(2).map(|b| f32::from(i16::from_le_bytes([b[0], b[1]])) / 32768.0).collect(),
+            (1, 24) => data.chunks_exact(3).map(|b| (f64::from(i32::from_le_bytes([0, b[0], b[1], b[2]])) / 2_147_483_648.0) as f32).collect(),
+            (1, 32) => data.chunks_exact(4).map(|b| (f64::from(i32::from_le_bytes([b[0], b[1], b[2], b[3]])) / 2_147_483_648.0) as f32).collect(),
+            (3, 32) => data.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect(),
+            (3, 64) => data.chunks_exact(8).map(|b| f64::from_le_bytes(b.try_into().unwrap_or_default()) as f32).collect(),
+            (fmt_type, bit_count) => {
+                return Err(GICSError::new(&format!("WAV sample format not supported: type {fmt_type}, {bit_count} bits")));
+            }
+        };
+        Ok(samples)
+    }
+}
+
+#[cfg(test)]
+mod wav_tests {
+    use super::*;
+
+    /// Little-endian 32-bit fields of `bytes`, past its chunk name
+    fn fields(bytes: &[u8]) -> Vec<u32> {
+        bytes[4..].chunks_exact(4).map(|field| u32::from_le_bytes([field[0], field[1], field[2], field[3]])).collect()
+    }
+
+    #[test]
+    fn loop_sample_chunk() {
+        let smpl = WaveSample::for_loop(48000, 0x1000, 0x3000).build_byte_array();
+        assert_eq!(&smpl[..4], b"smpl");
+        assert_eq!(smpl.len(), 8 + 0x3C);
+        // Size, manufacturer, product, sample period, unity note, pitch fraction, SMPTE format and offset, loop count,
+        // sampler data, then the loop : identifier, type, start, last sample, fraction and play count
+        assert_eq!(fields(&smpl), [0x3C, 0, 0, 20833, 60, 0, 0, 0, 1, 0, 0, 0, 0x1000, 0x2FFF, 0, 0]);
+    }
+
+    #[test]
+    fn extensible_header() {
+        let riff = WaveRiff::for_format(PCMFormat::Int24, 6, 48000);
+        let mut expected: Vec<u8> = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        expected.extend(0x28_u32.to_le_bytes());
+        // WAVE_FORMAT_EXTENSIBLE, 6 channels at 48 kHz of 3 bytes each
+        expected.extend(0xFFFE_u16.to_le_bytes());
+        expected.extend(6_u16.to_le_bytes());
+        expected.extend(48000_u32.to_le_bytes());
+        expected.extend((48000_u32 * 18).to_le_bytes());
+        expected.extend(18_u16.to_le_bytes());
+        expected.extend(24_u16.to_le_bytes());
+        // 22 bytes of extension : valid bits, 5.1 channel mask, then the PCM sub-format GUID
+        expected.extend(22_u16.to_le_bytes());
+        expected.extend(24_u16.to_le_bytes());
+        expected.extend(0x3F_u32.to_le_bytes());
+        expected.extend([0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71]);
+        assert_eq!(riff.build_byte_array(), expected);
+        assert_eq!(riff.header_size() as usize, expected.len() - 8);
+
+        // Stereo keeps the plain format
+        let riff = WaveRiff::for_format(PCMFormat::Float, 2, 48000).build_byte_array();
+        assert_eq!(riff.len(), 0x24);
+        assert_eq!(riff[0x14..0x16], 3_u16.to_le_bytes());
+    }
+}
@@ -0,0 +1,61 @@
+//! GI Cutscenes : Rust Remix
+//!
+//! Library behind the `gi-cutscenes-rs` command line tool. It exposes everything needed
+//! to work with the cutscene files (USM) from Genshin Impact :
+//!  - [`filetypes::USMFile`] and the [`demux::Demuxable`] trait to demux (and decrypt) USM files
+//!  - [`filetypes::USMReader`] to stream the (decrypted) chunks of a USM from any seekable source
+//!  - [`filetypes::USMMetadata`] and [`filetypes::UTFTable`] to read the stream metadata of a USM
+//!  - [`demux::StreamSink`] to route every demuxed stream to a writer or decoder of your choosing
+//!  - [`filetypes::HCAFile`] to decode (and decrypt) HCA audio, and write it as WAV
+//!  - [`filetypes::ADXFile`] to decode (and decrypt) ADX audio, and write it as WAV
+//!  - [`version`] to read `versions.json` key files and derive the keys of a cutscene
+//!  - [`filetypes::MKVFile`] to merge the demuxed streams into a MKV container, natively or with `ffmpeg`
+//!  - [`filetypes::IVFFile`] to read the frames of the demuxed video streams
+//!
+//! A typical use looks like :
+//! ```no_run
+//! use std::path::{Path, PathBuf};
+//! use gi_cutscenes_rs::{demux::Demuxable, filetypes::{HCAFile, USMFile}, version};
+//!
+//! # fn main() -> gi_cutscenes_rs::errors::GICSResult<()> {
+//! let keys = version::read_version_file("versions.json")?;
+//! let (key2, key1) = version::definite_version_keys("Cs_Sumeru_AQ30161501_DT.usm", Some(&keys), None, None)?;
+//! let usm = USMFile::new(PathBuf::from("Cs_Sumeru_AQ30161501_DT.usm"), key2.to_le_bytes(), key1.to_le_bytes())?;
+//! let (_video, audio) = usm.demux(true, true, Path::new("output"))?;
+//! for track in audio {
+//!     let wav = track.with_extension("wav");
+//!     HCAFile::new(&track, key2.to_le_bytes(), key1.to_le_bytes())?.convert_to_wav(&wav)?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+// Make clippy quite nasty
+#![deny(clippy::cargo)]         // Checks for garbage in the Cargo TOML files
+#![deny(clippy::complexity)]    // Checks for needlessly complex structures
+#![deny(clippy::correctness)]   // Checks for common invalid usage and workarounds
+#![deny(clippy::nursery)]       // Checks for things that are typically forgotten by learners
+#![deny(clippy::pedantic)]      // Checks for mildly annoying comments it could make about your code
+#![deny(clippy::perf)]          // Checks for inefficient ways to perform common tasks
+#![deny(clippy::style)]         // Checks for inefficient styling of code
+#![deny(clippy::suspicious)]    // Checks for potentially malicious behaviour
+// Add some new clippy lints
+#![deny(clippy::use_self)]      // Checks for the use of a struct's name in its `impl`
+// Add some default lints
+#![deny(unused_variables)]      // Checks for unused variables
+// Deny missing documentation
+#![deny(missing_docs)]
+#![deny(rustdoc::missing_crate_level_docs)]
+
+// Everything allowed here will go because it's bad number type management
+#![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_sign_loss)]
+// The error type is documented once, at its definition
+#![allow(clippy::missing_errors_doc)]
+
+pub mod demux;
+pub mod errors;
+pub mod filetypes;
+pub mod version;
+
+mod tools;
@@ -1,4 +1,6 @@
 //! CI Cutscenes Extractor : Rust Remix
+//!
+//! Command line front-end of the `gi_cutscenes_rs` library.
 
 // Make clippy quite nasty
 #![deny(clippy::cargo)]         // Checks for garbage in the Cargo TOML files
@@ -24,21 +26,181 @@
 //#![allow(dead_code)]
 
 use clap::{
-    Arg,
+    Arg, ArgMatches,
     Command, ValueHint
 };
 
-use std::path::{
-    Path,
-    PathBuf
+use std::path::{Path, PathBuf};
+
+use gi_cutscenes_rs::{
+    demux::{self, DamageReport, DemuxOptions, Progress},
+    errors::GICSResult,
+    filetypes::{
+        ASSStyle, AudioLanguages, BlockErrors, HCAEncoder, HCAEncoderOptions, HCAQuality, HCAProbe,
+        HCAVersion, PCMFormat, PCMOptions, USMProbe
+    },
+    version
 };
 
-mod demux;
-mod filetypes;
-mod errors;
-mod tools;
 mod validate;
-mod version;
+
+/// Gather the options shared by the demuxing subcommands
+fn demux_options(cmd: &ArgMatches, cleanup: bool) -> GICSResult<DemuxOptions> {
+    let mut subtitle_style = ASSStyle::default();
+    if let Some(font) = cmd.value_of("subtitle-font") {
+        subtitle_style.font_name = font.into();
+    }
+    // Clap already validated the size
+    if let Some(size) = cmd.value_of("subtitle-font-size") {
+        subtitle_style.font_size = size.parse().unwrap();
+    }
+    // The configuration file overrides the default languages, and the command line overrides both
+    let mut audio_languages = AudioLanguages::default();
+    if let Some(config) = cmd.value_of("language-config") {
+        audio_languages.read_file(&PathBuf::from(config))?;
+    }
+    for spec in cmd.values_of("audio-language").into_iter().flatten() {
+        audio_languages.set_from_spec(spec)?;
+    }
+    Ok(DemuxOptions {
+        merge: cmd.is_present("merge"),
+        cleanup,
+        ffmpeg_path: cmd.value_of("merge-program").map(String::from),
+        subtitles: cmd.value_of("subs").map(PathBuf::from),
+        subtitle_style,
+        audio_languages,
+        skip_key_check: cmd.is_present("skip-key-check"),
+        discover_keys: cmd.is_present("discover-keys"),
+        block_errors: block_errors(cmd),
+        pcm: pcm_options(cmd),
+        loop_count: 1
+    })
+}
+
+/// What to do with damaged HCA blocks, as chosen with `--damaged-blocks`
+fn block_errors(cmd: &ArgMatches) -> BlockErrors {
+    // Clap already validated the value
+    match cmd.value_of("damaged-blocks") {
+        Some("silence") => BlockErrors::Silence,
+        Some("repeat") => BlockErrors::Repeat,
+        _ => BlockErrors::Strict
+    }
+}
+
+/// Sample format and gain of the WAV files, as chosen with `--pcm-format` and `--gain`
+fn pcm_options(cmd: &ArgMatches) -> PCMOptions {
+    // Clap already validated the values
+    let format = match cmd.value_of("pcm-format") {
+        Some("8") => PCMFormat::Int8,
+        Some("24") => PCMFormat::Int24,
+        Some("32") => PCMFormat::Int32,
+        Some("float") => PCMFormat::Float,
+        _ => PCMFormat::Int16
+    };
+    let gain = cmd.value_of("gain").and_then(|s| s.parse::<f32>().ok()).unwrap_or(0.0);
+    PCMOptions { format, gain: 10_f32.powf(gain / 20.0) }
+}
+
+/// Layout of the HCA files written by `encodeHca`
+fn encoder_options(cmd: &ArgMatches) -> HCAEncoderOptions {
+    // Clap already validated the values
+    let key = |name: &str| cmd.value_of(name).and_then(|s| u32::from_str_radix(s, 16).ok()).unwrap_or(0);
+    HCAEncoderOptions {
+        version: if cmd.value_of("hca-version") == Some("3.0") { HCAVersion::V3_0 } else { HCAVersion::V2_0 },
+        dec_chunk: cmd.is_present("dec-chunk"),
+        ath_type: cmd.value_of("ath").and_then(|s| s.parse::<u16>().ok()),
+        cipher_type: cmd.value_of("cipher").and_then(|s| s.parse::<u16>().ok()),
+        key2: key("key2"),
+        key1: key("key1"),
+        quality: match cmd.value_of("quality") {
+            Some("highest") => HCAQuality::Highest,
+            Some("middle") => HCAQuality::Middle,
+            Some("low") => HCAQuality::Low,
+            Some("lowest") => HCAQuality::Lowest,
+            _ => HCAQuality::High
+        }
+    }
+}
+
+/// Keys of a HCA file, as `(key2, key1)`, given with `-a`/`-b` or found in the key file under `basename`.
+///
+/// Errors are printed, and nothing is returned
+fn hca_keys(cmd: &ArgMatches, basename: &str) -> Option<(u32, u32)> {
+    let key_one: Option<u32> = cmd.value_of("key1").map(|s| u32::from_str_radix(s, 16).unwrap());
+    let key_two: Option<u32> = cmd.value_of("key2").map(|s| u32::from_str_radix(s, 16).unwrap());
+    // We haven't validated the file here
+    let version_file: &str = cmd.value_of("version-keys").unwrap();
+    let version_keys: Option<Vec<version::Data>> = if key_one.is_none() || key_two.is_none() {
+            // Let's validate that the file exists
+            if let Err(e) = validate::is_file(version_file) {
+                eprintln!("Error opening version keys file : {e}");
+                return None;
+            }
+            match version::read_version_file(PathBuf::from(version_file)) {
+                Ok(keydata) => Some(keydata),
+                Err(e) => {
+                    eprintln!("Error reading key file : {e}");
+                    return None;
+                }
+            }
+        } else { None };
+
+    // Get our keys
+    let (key_two, key_one) = match version::definite_version_keys(basename, version_keys.as_deref(), key_one, key_two) {
+        Ok(keys) => keys,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return None;
+        }
+    };
+    // Unless the key file says the audio is not encrypted
+    Some(if version::audio_encrypted(basename, version_keys.as_deref()) { (key_two, key_one) } else { (0, 0) })
+}
+
+/// Path of a HCA file written next to `file`, named after it with a suffix
+fn suffixed(file: &Path, suffix: &str) -> PathBuf {
+    let stem = file.file_stem().and_then(std::ffi::OsStr::to_str).unwrap_or_default();
+    file.with_file_name(format!("{stem}_{suffix}.hca"))
+}
+
+/// Warn about the blocks concealed in the audio streams demuxed
+fn warn_concealed(report: &DamageReport) {
+    for (name, damaged) in report {
+        eprintln!("Warning: {name} : {damaged}, concealed");
+    }
+}
+
+/// Report a step of the demuxing pipelines, warnings going to the standard error
+fn report(progress: Progress) {
+    match progress {
+        Progress::KeyFound(name, candidate) => println!("Key of \"{}\" {candidate}", name.split('.').next().unwrap_or_default()),
+        Progress::KeysDerived(name, key2, key1, encrypted_audio) => println!("Keys derived for \"{name}\" : ({key2:08X}, {key1:08X}){}",
+            if encrypted_audio { "" } else { ", audio not encrypted" }),
+        Progress::VideoTrack(video) => println!("Video track #{} : {} {}x{} at {:.2} fps, {} frames",
+            video.chno, video.codec_name(), video.width, video.height,
+            video.frame_rate().unwrap_or_default(), video.total_frames),
+        Progress::AudioTrack(chno, codec) => println!("Processing track #{chno} ({codec}).."),
+        Progress::UncheckedKeys(stem) => eprintln!("Warning: unable to check the keys of \"{stem}\", the output may be garbage"),
+        Progress::Demuxed(videos, audios) => println!("File demuxed. Collected {videos} video and {audios} audio files."),
+        Progress::EmbeddedSubtitles(count) => println!("Extracted {count} embedded subtitle tracks."),
+        Progress::ConvertedSubtitles(count) => println!("Converted {count} SRT subtitle files."),
+        Progress::MissingAudio(announced, found) =>
+            eprintln!("Warning: the file headers announce {announced} audio tracks, but {found} were found")
+    }
+}
+
+/// Files given to `name`, either directly or as folders holding them, listed with `files_in`
+fn inputs(cmd: &ArgMatches, name: &str, files_in: fn(&Path) -> GICSResult<Vec<PathBuf>>) -> GICSResult<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for input in cmd.values_of(name).unwrap().map(PathBuf::from) {
+        if input.is_dir() {
+            files.extend(files_in(&input)?);
+        } else {
+            files.push(input);
+        }
+    }
+    Ok(files)
+}
 
 #[allow(clippy::too_many_lines)]
 fn main() {
@@ -61,7 +223,66 @@ fn main() {
     let subs_option = Arg::new("subs")
         .short('s')
         .long("subtitles")
-        .help("Adds the substitles to the MKV file");
+        .value_name("subtitles")
+        .help("SRT file, or subtitle root folder of <Cutscene>_<LANG>.srt files (one folder per language), converted to ASS and merged with the streams")
+        .takes_value(true)
+        .validator(|s| validate::is_file(s).map(|_| ()).or_else(|_| validate::is_dir(s)))
+        .value_hint(ValueHint::AnyPath);
+    let subs_font_option = Arg::new("subtitle-font")
+        .long("subtitle-font")
+        .value_name("font")
+        .help("Font of the converted subtitles")
+        .takes_value(true);
+    let subs_size_option = Arg::new("subtitle-font-size")
+        .long("subtitle-font-size")
+        .value_name("size")
+        .help("Font size of the converted subtitles, for a 1920x1080 picture")
+        .takes_value(true)
+        .validator(str::parse::<u32>);
+    let audio_language_option = Arg::new("audio-language")
+        .short('l')
+        .long("audio-language")
+        .value_name("chno=language[:title]")
+        .help("Labels an audio channel with an ISO 639-2 language code and a title (e.g. 4=fre:French)")
+        .takes_value(true)
+        .multiple_occurrences(true)
+        .validator(|s| AudioLanguages::default().set_from_spec(s));
+    let language_config_option = Arg::new("language-config")
+        .long("language-config")
+        .value_name("language_config")
+        .help("JSON file labelling the audio channels, as { \"audioLanguages\": { \"4\": { \"language\": \"fre\", \"title\": \"French\" } } }")
+        .takes_value(true)
+        .validator(|s| validate::is_file(s))
+        .value_hint(ValueHint::FilePath);
+    let skip_key_check_option = Arg::new("skip-key-check")
+        .long("skip-key-check")
+        .help("Decrypts everything without checking the keys against the start of the streams first");
+    let damaged_blocks_option = Arg::new("damaged-blocks")
+        .long("damaged-blocks")
+        .value_name("damaged_blocks")
+        .help("What to do with damaged HCA blocks : stop with an error, or replace them with silence or the last block")
+        .takes_value(true)
+        .possible_values(["strict", "silence", "repeat"])
+        .default_value("strict");
+    let pcm_format_option = Arg::new("pcm-format")
+        .long("pcm-format")
+        .value_name("pcm_format")
+        .help("Sample format of the WAV files : 8, 16, 24 or 32-bit integers, or 32-bit floats")
+        .takes_value(true)
+        .possible_values(["8", "16", "24", "32", "float"])
+        .default_value("16");
+    let gain_option = Arg::new("gain")
+        .long("gain")
+        .value_name("dB")
+        .help("Gain applied to the decoded audio, in decibels")
+        .takes_value(true)
+        .allow_hyphen_values(true)
+        .validator(|s| s.parse::<f32>().ok().filter(|g| g.is_finite()).map(|_| ()).ok_or("Invalid gain"))
+        .default_value("0");
+    let discover_keys_option = Arg::new("discover-keys")
+        .short('d')
+        .long("discover-keys")
+        .help("Finds the keys by trying every key of the key file, for files it does not list (or lists wrongly)");
     let merge_option = Arg::new("merge")
         .short('m')
         .long("merge")
@@ -70,14 +291,27 @@ fn main() {
         .short('p')
         .long("merge-program")
         .takes_value(true)
-        .default_value("ffmpeg")
-        .help("Path to the ffmpeg binary used to merge the output files");
+        .help("Path to the ffmpeg binary used to merge the output files (merged natively if not given)");
     let version_json = Arg::new("version-keys")
         .short('k')
         .long("version-keys")
         .help("Provide a path to a valid version.json file containing the version keys")
         .takes_value(true)
         .default_value("version.json");
+    let basename_option = Arg::new("basename")
+        .short('n')
+        .long("base-name")
+        .value_name("base_name")
+        .help("Base name of the file (to find its cutscene in versions.json)")
+        .takes_value(true);
+    let hca_input_option = Arg::new("hca-input")
+        .short('i')
+        .long("hca-input")
+        .value_name("hca_input")
+        .help("File or directory to be processed")
+        .takes_value(true)
+        .validator(|s| validate::is_hca_file(s))
+        .value_hint(ValueHint::FilePath);
     let args = Command::new("GI Cutscenes")
         .version("0.1.0")
         .author("Lux A. Phifollen <contact@vulpinecitrus.info>")
@@ -100,6 +334,15 @@ fn main() {
                 .arg(key2.clone())
                 .arg(version_json.clone())
                 .arg(subs_option.clone())
+                .arg(subs_font_option.clone())
+                .arg(subs_size_option.clone())
+                .arg(audio_language_option.clone())
+                .arg(language_config_option.clone())
+                .arg(skip_key_check_option.clone())
+                .arg(discover_keys_option.clone())
+                .arg(damaged_blocks_option.clone())
+                .arg(pcm_format_option.clone())
+                .arg(gain_option.clone())
                 .arg(merge_option.clone())
                 .arg(ffmpeg_option.clone())
         )
@@ -116,30 +359,157 @@ fn main() {
                     .value_hint(ValueHint::DirPath))
                 .arg(version_json.clone())
                 .arg(subs_option)
+                .arg(subs_font_option)
+                .arg(subs_size_option)
+                .arg(audio_language_option)
+                .arg(language_config_option)
+                .arg(skip_key_check_option.clone())
+                .arg(discover_keys_option)
+                .arg(damaged_blocks_option.clone())
+                .arg(pcm_format_option.clone())
+                .arg(gain_option.clone())
                 .arg(merge_option)
                 .arg(ffmpeg_option)
         )
         .subcommand(
             Command::new("convertHca")
                 .about("Converts input .hca files into .wav files")
-                .arg(Arg::new("hca-input")
+                .arg(hca_input_option.clone())
+                .arg(basename_option.clone())
+                .arg(key1.clone())
+                .arg(key2.clone())
+                .arg(version_json.clone())
+                .arg(skip_key_check_option.clone())
+                .arg(damaged_blocks_option.clone())
+                .arg(pcm_format_option)
+                .arg(gain_option)
+                .arg(Arg::new("loops")
+                    .long("loops")
+                    .value_name("count")
+                    .help("Times the loop of looping files is played (their loop is marked in the WAV file either way)")
+                    .takes_value(true)
+                    .validator(|s| s.parse::<u32>().ok().filter(|&count| count > 0).map(|_| ()).ok_or("The loop count must be a positive integer"))
+                    .default_value("1"))
+        )
+        .subcommand(
+            Command::new("encodeHca")
+                .about("Encodes an input .wav file into a .hca file")
+                .arg(Arg::new("wav-input")
                     .short('i')
-                    .long("hca-input")
-                    .value_name("hca_input")
-                    .help("File or directory to be processed")
+                    .long("wav-input")
+                    .value_name("wav_input")
+                    .help("PCM WAV file to be encoded")
+                    .required(true)
                     .takes_value(true)
-                    .validator(|s| validate::is_hca_file(s))
+                    .validator(|s| validate::is_wav_file(s))
                     .value_hint(ValueHint::FilePath))
-                .arg(Arg::new("basename")
-                    .short('n')
-                    .long("base-name")
-                    .value_name("base_name")
-                    .help("Base name of the file (to find its cutscene in versions.json)")
+                .arg(key1.clone())
+                .arg(key2.clone())
+                .arg(Arg::new("hca-version")
+                    .long("hca-version")
+                    .value_name("version")
+                    .help("Version of the HCA header")
+                    .takes_value(true)
+                    .possible_values(["2.0", "3.0"])
+                    .default_value("2.0"))
+                .arg(Arg::new("dec-chunk")
+                    .long("dec-chunk")
+                    .help("Describes the compression with a dec chunk, as older files do, instead of a comp chunk"))
+                .arg(Arg::new("ath")
+                    .long("ath")
+                    .value_name("ath_type")
+                    .help("Writes an ath chunk of that type (type 0 if not given)")
+                    .takes_value(true)
+                    .possible_values(["0", "1"]))
+                .arg(Arg::new("cipher")
+                    .long("cipher")
+                    .value_name("cipher_type")
+                    .help("Writes a ciph chunk of that type, type 56 encrypting with the key given (not encrypted if not given)")
                     .takes_value(true)
-                )
-                .arg(key1)
-                .arg(key2)
+                    .possible_values(["0", "1", "56"]))
+                .arg(Arg::new("quality")
+                    .short('q')
+                    .long("quality")
+                    .value_name("quality")
+                    .help("Bitrate and bandwidth of the encoded audio")
+                    .takes_value(true)
+                    .possible_values(["highest", "high", "middle", "low", "lowest"])
+                    .default_value("high"))
+        )
+        .subcommand(
+            Command::new("decryptHca")
+                .about("Decrypts an input .hca file into a plain .hca file, without decoding it")
+                .arg(hca_input_option.clone().required(true))
+                .arg(basename_option)
+                .arg(key1.clone())
+                .arg(key2.clone())
                 .arg(version_json)
+                .arg(skip_key_check_option)
+                .arg(damaged_blocks_option.clone())
+        )
+        .subcommand(
+            Command::new("encryptHca")
+                .about("Encrypts a plain input .hca file with a key (cipher type 56), without decoding it")
+                .arg(hca_input_option.required(true))
+                .arg(key1.required(true))
+                .arg(key2.required(true))
+                .arg(damaged_blocks_option)
+        )
+        .subcommand(
+            Command::new("recoverKey")
+                .about("Recovers the keys of .usm files missing from the key file")
+                .arg(Arg::new("recover-input")
+                    .short('i')
+                    .long("input")
+                    .value_name("input")
+                    .help(".usm file, or folder containing .usm files, whose keys are recovered")
+                    .required(true)
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .validator(|s| validate::is_usm_file(s).or_else(|_| validate::is_dir(s)))
+                    .value_hint(ValueHint::AnyPath))
+                .arg(Arg::new("version-name")
+                    .long("version-name")
+                    .value_name("version_name")
+                    .help("Version given to the key file entries printed")
+                    .takes_value(true)
+                    .default_value("new"))
+        )
+        .subcommand(
+            Command::new("probeUsm")
+                .about("Prints the stream layout of .usm files, without writing anything")
+                .arg(Arg::new("probe-input")
+                    .short('i')
+                    .long("input")
+                    .value_name("input")
+                    .help(".usm file, or folder containing .usm files, to be probed")
+                    .required(true)
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .validator(|s| validate::is_usm_file(s).or_else(|_| validate::is_dir(s)))
+                    .value_hint(ValueHint::AnyPath))
+                .arg(Arg::new("json")
+                    .short('j')
+                    .long("json")
+                    .help("Prints the layout as JSON"))
+        )
+        .subcommand(
+            Command::new("inspectHca")
+                .about("Prints the header of .hca files, without decoding anything")
+                .arg(Arg::new("inspect-input")
+                    .short('i')
+                    .long("input")
+                    .value_name("input")
+                    .help(".hca file, or folder containing .hca files, to be inspected")
+                    .required(true)
+                    .takes_value(true)
+                    .multiple_occurrences(true)
+                    .validator(|s| validate::is_hca_file(s).or_else(|_| validate::is_dir(s)))
+                    .value_hint(ValueHint::AnyPath))
+                .arg(Arg::new("json")
+                    .short('j')
+                    .long("json")
+                    .help("Prints the headers as JSON"))
         )
         .arg(Arg::new("output")
              .short('o')
@@ -166,9 +536,13 @@ fn main() {
                 .into();
             let key_one: Option<u32> = cmd.value_of("key1").map(|s| u32::from_str_radix(s, 16).unwrap());
             let key_two: Option<u32> = cmd.value_of("key2").map(|s| u32::from_str_radix(s, 16).unwrap());
-            let subs: bool = cmd.is_present("subs"); // This will become a path later
-            let merge: bool = cmd.is_present("merge");
-            let ffmpeg_path: &str = cmd.value_of("merge-program").unwrap();
+            let options = match demux_options(cmd, cleanup) {
+                Ok(options) => options,
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    return;
+                }
+            };
             let output: PathBuf = args.value_of("output")
                 // No need to re-validate since we know the file is good, its folder must be too
                 .map_or_else(
@@ -187,23 +561,42 @@ fn main() {
             let version_keys: Option<Vec<version::Data>> = if key_one.is_none() || key_two.is_none() {
                     // Let's validate that the file exists
                     if let Err(e) = validate::is_file(version_file) {
-                        eprintln!("Error opening version keys file : {}", e);
+                        eprintln!("Error opening version keys file : {e}");
                         return;
                     }
                     match version::read_version_file(PathBuf::from(version_file)) {
                         Ok(keydata) => Some(keydata),
                         Err(e) => {
-                            eprintln!("Error reading key file : {}", e);
+                            eprintln!("Error reading key file : {e}");
                             return;
                         }
                     }
                 } else { None };
 
-            let (key_two, key_one) = version::definite_version_keys(&basename, version_keys.as_deref(), key_one, key_two).unwrap();
-            println!("Keys derived for \"{}\" : ({:08X}, {:08X})", file.to_str().unwrap(), key_two, key_one);
-            let res = demux::process_file(file, key_two, key_one, output.as_path(), merge, cleanup, subs, ffmpeg_path);
-            if let Err(e) = res {
-                eprintln!("Error: {}", e);
+            // The key file is only read without explicit keys, which are used as is
+            let (key_two, key_one, encrypted_audio) = match version_keys.as_deref() {
+                Some(vkeys) if options.discover_keys => match demux::discover_keys(&file, vkeys) {
+                    Ok((candidate, _)) => {
+                        report(Progress::KeyFound(&basename, &candidate));
+                        (candidate.key2, candidate.key1, candidate.encrypted_audio)
+                    },
+                    Err(e) => {
+                        eprintln!("Error: {e}");
+                        std::process::exit(1);
+                    }
+                },
+                _ => match version::definite_version_keys(&basename, version_keys.as_deref(), key_one, key_two) {
+                    Ok((key_two, key_one)) => (key_two, key_one, version::audio_encrypted(&basename, version_keys.as_deref())),
+                    Err(e) => {
+                        eprintln!("Error: {e}");
+                        std::process::exit(1);
+                    }
+                }
+            };
+            report(Progress::KeysDerived(&file.to_string_lossy(), key_two, key_one, encrypted_audio));
+            match demux::process_file(file, key_two, key_one, encrypted_audio, output.as_path(), &options, &mut report) {
+                Ok(report) => warn_concealed(&report),
+                Err(e) => eprintln!("Error: {e}")
             }
 
         },
@@ -211,9 +604,13 @@ fn main() {
             // Start to extract the arguments
             // Clap already validated the paths and the key values if any
             let folder: PathBuf = PathBuf::from(cmd.value_of("usm-folder").unwrap());
-            let subs: bool = cmd.is_present("subs");
-            let merge: bool = cmd.is_present("merge");
-            let ffmpeg_path: &str = cmd.value_of("merge-program").unwrap();
+            let options = match demux_options(cmd, cleanup) {
+                Ok(options) => options,
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    return;
+                }
+            };
             let output: PathBuf = args.value_of("output")
                 // No need to re-validate since we know the file is good, its folder must be too
                 .map_or_else(
@@ -228,14 +625,15 @@ fn main() {
             let version_keys: Vec<version::Data> = match version::read_version_file(PathBuf::from(version_file)) {
                 Ok(keydata) => keydata,
                 Err(e) => {
-                    eprintln!("Error reading key file : {}", e);
+                    eprintln!("Error reading key file : {e}");
                     return;
                 }
             };
 
             // Start working through the directory..
-            if let Err(e) = demux::process_directory(folder, &version_keys, output.as_path(), merge, cleanup, subs, ffmpeg_path) {
-                eprintln!("Error: {}", e);
+            match demux::process_directory(&folder, &version_keys, output.as_path(), &options, &mut report) {
+                Ok(report) => warn_concealed(&report),
+                Err(e) => eprintln!("Error: {e}")
             }
         },
         Some(("convertHca", cmd)) => {
@@ -260,31 +658,156 @@ fn main() {
                     // If we do, attempt to get a path from it
                     PathBuf::from
                 );
-            let key_one: Option<u32> = cmd.value_of("key1").map(|s| u32::from_str_radix(s, 16).unwrap());
-            let key_two: Option<u32> = cmd.value_of("key2").map(|s| u32::from_str_radix(s, 16).unwrap());
-            // We haven't validated the file here
-            let version_file: &str = cmd.value_of("version-keys").unwrap();
-            let version_keys: Option<Vec<version::Data>> = if key_one.is_none() || key_two.is_none() {
-                    // Let's validate that the file exists
-                    if let Err(e) = validate::is_file(version_file) {
-                        eprintln!("Error opening version keys file : {}", e);
-                        return;
+            let Some((key_two, key_one)) = hca_keys(cmd, basename) else {
+                std::process::exit(1);
+            };
+
+            // Convert
+            let options = DemuxOptions {
+                cleanup,
+                skip_key_check: cmd.is_present("skip-key-check"),
+                block_errors: block_errors(cmd),
+                pcm: pcm_options(cmd),
+                loop_count: cmd.value_of("loops").and_then(|s| s.parse::<u32>().ok()).unwrap_or(1),
+                ..DemuxOptions::default()
+            };
+            match demux::process_hca(file.clone(), key_two, key_one, output.as_path(), &options) {
+                Ok((_, damaged)) if !damaged.is_empty() => eprintln!("Warning: \"{}\" : {damaged}, concealed", file.display()),
+                Ok(_) => {},
+                Err(e) => eprintln!("Error: {e}")
+            }
+        },
+        Some(("encodeHca", cmd)) => {
+            // Clap already validated the path and the key values if any
+            let file: PathBuf = PathBuf::from(cmd.value_of("wav-input").unwrap());
+            let output: PathBuf = args.value_of("output").map_or_else(|| file.with_extension("hca"), PathBuf::from);
+            if cmd.value_of("cipher") == Some("56") && !(cmd.is_present("key1") && cmd.is_present("key2")) {
+                eprintln!("Error: cipher type 56 needs both halves of the key");
+                return;
+            }
+            match HCAEncoder::new(encoder_options(cmd)).and_then(|encoder| encoder.encode_file(&file, &output)) {
+                Ok(path) => println!("Encoded \"{}\"", path.display()),
+                Err(e) => eprintln!("Error: {e}")
+            }
+        },
+        Some(("decryptHca", cmd)) => {
+            // Clap already validated the path and the key values if any
+            let file: PathBuf = PathBuf::from(cmd.value_of("hca-input").unwrap());
+            let basename: &str = cmd.value_of("basename")
+                .unwrap_or_else(|| file.file_name().and_then(std::ffi::OsStr::to_str).unwrap_or_default());
+            let output: PathBuf = args.value_of("output").map_or_else(|| suffixed(&file, "decrypted"), PathBuf::from);
+            let Some((key_two, key_one)) = hca_keys(cmd, basename) else {
+                std::process::exit(1);
+            };
+            let options = DemuxOptions {
+                skip_key_check: cmd.is_present("skip-key-check"),
+                block_errors: block_errors(cmd),
+                ..DemuxOptions::default()
+            };
+            match demux::decrypt_hca(&file, key_two, key_one, &output, &options) {
+                Ok((path, damaged)) => {
+                    if !damaged.is_empty() {
+                        eprintln!("Warning: \"{}\" : {damaged}, copied as they are", file.display());
                     }
-                    match version::read_version_file(PathBuf::from(version_file)) {
-                        Ok(keydata) => Some(keydata),
-                        Err(e) => {
-                            eprintln!("Error reading key file : {}", e);
-                            return;
-                        }
+                    println!("Decrypted \"{}\"", path.display());
+                },
+                Err(e) => eprintln!("Error: {e}")
+            }
+        },
+        Some(("encryptHca", cmd)) => {
+            // Clap already validated the path and the key values
+            let file: PathBuf = PathBuf::from(cmd.value_of("hca-input").unwrap());
+            let output: PathBuf = args.value_of("output").map_or_else(|| suffixed(&file, "encrypted"), PathBuf::from);
+            let key = |name: &str| cmd.value_of(name).and_then(|s| u32::from_str_radix(s, 16).ok()).unwrap_or(0);
+            let options = DemuxOptions {
+                block_errors: block_errors(cmd),
+                ..DemuxOptions::default()
+            };
+            match demux::encrypt_hca(&file, key("key2"), key("key1"), &output, &options) {
+                Ok((path, damaged)) => {
+                    if !damaged.is_empty() {
+                        eprintln!("Warning: \"{}\" : {damaged}, copied as they are", file.display());
                     }
-                } else { None };
+                    println!("Encrypted \"{}\"", path.display());
+                },
+                Err(e) => eprintln!("Error: {e}")
+            }
+        },
+        Some(("recoverKey", cmd)) => {
+            let files = match inputs(cmd, "recover-input", demux::usm_files_in) {
+                Ok(files) => files,
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    return;
+                }
+            };
+            // Files of a same version share their blk key, and go in the same entry
+            let mut entries: Vec<(u64, Vec<String>)> = Vec::new();
+            for file in files {
+                let stem: String = file.file_stem().and_then(std::ffi::OsStr::to_str).unwrap_or_default().into();
+                match demux::recover_key(&file) {
+                    Ok(candidate) => {
+                        println!("Keys of \"{stem}\" : ({:08X}, {:08X}), blk key {}", candidate.key2, candidate.key1, candidate.blk_key);
+                        match entries.iter_mut().find(|(key, _)| *key == candidate.blk_key) {
+                            Some((_, videos)) => videos.push(stem),
+                            None => entries.push((candidate.blk_key, vec![stem]))
+                        }
+                    },
+                    Err(e) => eprintln!("Error: {e}")
+                }
+            }
+            // Entries are printed in the order of the key file fields, ready to be pasted in it
+            let version_name = serde_json::Value::from(cmd.value_of("version-name").unwrap());
+            for (key, videos) in entries {
+                println!("{{\"version\": {version_name}, \"videos\": {}, \"key\": {key}}}", serde_json::Value::from(videos));
+            }
+        },
+        Some(("probeUsm", cmd)) => {
+            let files = match inputs(cmd, "probe-input", demux::usm_files_in) {
+                Ok(files) => files,
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    return;
+                }
+            };
 
-            // Get our keys
-            let (key_one, key_two) = version::definite_version_keys(basename, version_keys.as_deref(), key_one, key_two).unwrap();
+            let mut probes: Vec<USMProbe> = Vec::new();
+            for file in files {
+                match USMProbe::from_path(&file) {
+                    Ok(probe) if cmd.is_present("json") => probes.push(probe),
+                    Ok(probe) => print!("{probe}"),
+                    Err(e) => eprintln!("Error probing \"{}\" : {e}", file.display())
+                }
+            }
+            if cmd.is_present("json") {
+                match serde_json::to_string_pretty(&probes) {
+                    Ok(json) => println!("{json}"),
+                    Err(e) => eprintln!("Error: {e}")
+                }
+            }
+        },
+        Some(("inspectHca", cmd)) => {
+            let files = match inputs(cmd, "inspect-input", demux::hca_files_in) {
+                Ok(files) => files,
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    return;
+                }
+            };
 
-            // Convert
-            if let Err(e) = demux::process_hca(file, key_two, key_one, output.as_path(), cleanup) {
-                eprintln!("Error: {}", e);
+            let mut probes: Vec<HCAProbe> = Vec::new();
+            for file in files {
+                match HCAProbe::from_path(&file) {
+                    Ok(probe) if cmd.is_present("json") => probes.push(probe),
+                    Ok(probe) => print!("{probe}"),
+                    Err(e) => eprintln!("Error inspecting \"{}\" : {e}", file.display())
+                }
+            }
+            if cmd.is_present("json") {
+                match serde_json::to_string_pretty(&probes) {
+                    Ok(json) => println!("{json}"),
+                    Err(e) => eprintln!("Error: {e}")
+                }
             }
         },
         _ => { eprintln!("No subcommand provided"); }
@@ -62,6 +62,12 @@ pub fn is_hca_file<T: Into<PathBuf>>(path: T) -> Result<()>
     has_right_extension(path, "hca")
 }
 
+pub fn is_wav_file<T: Into<PathBuf>>(path: T) -> Result<()>
+    where PathBuf: From<T>
+{
+    has_right_extension(path, "wav")
+}
+
 /// Verify that the path provided points to an existing directory
 pub fn is_dir<T: Into<PathBuf>>(path: T) -> Result<()>
     where PathBuf: From<T>
@@ -1,4 +1,10 @@
+//! Key derivation from the cutscene file names and the `versions.json` key file
+
 use std::{
+    fmt::{
+        Display,
+        Formatter
+    },
     io::{
         Error,
         ErrorKind,
@@ -13,40 +19,122 @@ use crate::errors::{
     GICSResult
 };
 
+/// A group of videos sharing a key, in the newer schema of the key file
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct VideoGroup {
+    videos: Vec<String>,
+    key: u64,
+    enc_audio: Option<bool>
+}
+
+/// One entry of the `versions.json` key file.
+///
+/// Entries either list their `videos` under a single `key`, or split them into `videoGroups` with a key each
+/// (or both). Their audio is encrypted with the video keys unless `encAudio` is `false`
 #[derive(Deserialize, Clone)]
-#[allow(dead_code)]
+#[serde(rename_all = "camelCase")]
 pub struct Data {
     version: String,
+    #[serde(default)]
     videos: Vec<String>,
-    key: u64
+    key: Option<u64>,
+    #[serde(default)]
+    video_groups: Vec<VideoGroup>,
+    enc_audio: Option<bool>
 }
 
 impl Data {
-    pub const fn get_key(&self) -> u64 {
+    /// Version of the game the entry describes
+    #[must_use]
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Key shared by the videos listed directly in the entry, if any
+    #[must_use]
+    pub const fn get_key(&self) -> Option<u64> {
         self.key
     }
 
+    /// Whether the video (without its extension) belongs to this version
+    #[must_use]
     pub fn contains_video(&self, name: &str) -> bool {
-        let name_str = name.into();
-        self.videos.contains(&name_str)
+        self.find_video(name).is_some()
+    }
+
+    /// Key of the video (without its extension), and whether its audio is encrypted
+    #[must_use]
+    pub fn find_video(&self, name: &str) -> Option<(u64, bool)> {
+        let entry_audio = self.enc_audio.unwrap_or(true);
+        if let Some(key) = self.key.filter(|_| self.videos.iter().any(|video| video == name)) {
+            return Some((key, entry_audio));
+        }
+        self.video_groups.iter()
+            .find(|group| group.videos.iter().any(|video| video == name))
+            .map(|group| (group.key, group.enc_audio.unwrap_or(entry_audio)))
+    }
+
+    /// Check that the entry can give a key to every video it lists
+    fn validate(&self) -> std::result::Result<(), String> {
+        if self.videos.is_empty() && self.video_groups.is_empty() {
+            return Err("lists no \"videos\" nor \"videoGroups\"".into());
+        }
+        if !self.videos.is_empty() && self.key.is_none() {
+            return Err("lists \"videos\" but has no \"key\" for them".into());
+        }
+        Ok(())
     }
 }
 
+/// Read the list of version keys from a `versions.json` file.
+///
+/// Both the original schema (`{"list": [{version, videos, key}]}`) and the newer one, with `videoGroups`
+/// and `encAudio`, are accepted
 pub fn read_version_file<T: AsRef<Path>>(path: T) -> Result<Vec<Data>>
 {
+    let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
     let content = std::fs::read(path)?;
     let data: String = String::from_utf8(content)
         .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
-    let parsed: std::collections::HashMap<String, Vec<Data>> = serde_json::from_str(&data)
-        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
-    for (key, val) in parsed {
-        if key == "list" {
-            return Ok(val);
-        }
+    let parsed: serde_json::Value = serde_json::from_str(&data)
+        .map_err(|e| invalid(format!("Key file is not valid JSON : {e}")))?;
+    let list = parsed.as_object()
+        .ok_or_else(|| invalid("Key file should be an object holding a \"list\" of versions".into()))?
+        .get("list")
+        .ok_or_else(|| invalid("Missing \"list\" key : the key file should look like {\"list\": [{\"version\": ..., \"videos\": [...], \"key\": ...}]}".into()))?
+        .as_array()
+        .ok_or_else(|| invalid("\"list\" should be an array of versions".into()))?;
+
+    let mut res: Vec<Data> = Vec::with_capacity(list.len());
+    for (index, value) in list.iter().enumerate() {
+        // Name the entry by its version if it has one, for the error messages
+        let name = value.get("version").and_then(serde_json::Value::as_str)
+            .map_or_else(|| format!("#{index}"), |version| format!("#{index} (version {version})"));
+        let entry = Data::deserialize(value)
+            .map_err(|e| invalid(format!("Invalid key file entry {name} : {e}")))?;
+        entry.validate()
+            .map_err(|e| invalid(format!("Invalid key file entry {name} : it {e}")))?;
+        res.push(entry);
     }
-    Err(Error::new(ErrorKind::InvalidData, "Missing list key"))
+    Ok(res)
 }
 
+/// Whether the audio of a file is encrypted with its keys, from the `encAudio` flags of the key file.
+///
+/// Audio is assumed to be encrypted unless the key file says otherwise
+#[must_use]
+pub fn audio_encrypted(filename: &str, version_keys: Option<&[Data]>) -> bool {
+    let basename: &str = filename.split('.').next().unwrap_or(filename);
+    version_keys
+        .and_then(|vkeys| vkeys.iter().find_map(|data| data.find_video(basename)))
+        .is_none_or(|(_, encrypted)| encrypted)
+}
+
+/// Find the keys of a file, returned as `(key2, key1)`.
+///
+/// Explicit keys take precedence over the key file. Otherwise, the keys are derived
+/// from `filename` and the entry of `version_keys` containing it
 pub fn definite_version_keys(filename: &str, version_keys: Option<&[Data]>, key1: Option<u32>, key2: Option<u32>) -> GICSResult<(u32, u32)> {
     match (key1, key2) {
         (Some(k1), Some(k2)) => Ok((k2, k1)),
@@ -66,13 +154,92 @@ fn find_key(filename: &str, version_keys: &[Data]) -> GICSResult<u64>
     let key1: u64 = file_name_encryption_key(filename);
     //let (key2, bld) = blk_encryption_key(filename);
     let key2: u64 = blk_encryption_key(filename, version_keys)?;
+    Ok(combine_keys(key1, key2))
+}
 
+/// Combine the key derived from a file name with a `blk` key from the key file
+const fn combine_keys(name_key: u64, blk_key: u64) -> u64 {
+    let sum = name_key.wrapping_add(blk_key);
     //if (key1 + key2 & 0xFF_FFFF_FFFF_FFFF) == 0 {
-    Ok(if (key1+key2).trailing_zeros() >= 56 {
+    if sum.trailing_zeros() >= 56 {
         0x100_0000_0000_0000
     } else {
-        (key1 + key2) & 0xFF_FFFF_FFFF_FFFF
-    })
+        sum & 0xFF_FFFF_FFFF_FFFF
+    }
+}
+
+/// A key a file could be encrypted with, and where it comes from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyCandidate {
+    /// Version of the key file entry the `blk` key comes from, or `None` for the key derived from the file name alone
+    pub version: Option<String>,
+    /// The `blk` key, as found in the key file
+    pub blk_key: u64,
+    /// 4 higher bytes of the key
+    pub key2: u32,
+    /// 4 lower bytes of the key
+    pub key1: u32,
+    /// Whether the audio is encrypted, according to the key file entry
+    pub encrypted_audio: bool,
+    /// Whether the key file entry lists the file among its videos
+    pub listed: bool
+}
+
+/// Where the key comes from, so that the key file can be updated
+impl Display for KeyCandidate {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.version {
+            Some(version) if !self.listed =>
+                write!(fmt, "found in version {version} (blk key {}) : add it to the videos of that version in the key file", self.blk_key),
+            Some(version) => write!(fmt, "found in version {version} (blk key {})", self.blk_key),
+            None => write!(fmt, "derived from its name alone (blk key 0)")
+        }
+    }
+}
+
+/// Every key a file could be encrypted with : every `blk` key of the key file combined with its name, then the key
+/// derived from its name alone.
+///
+/// Keys of the entries listing the file come first, and every key is only given once
+#[must_use]
+pub fn candidate_keys(filename: &str, version_keys: &[Data]) -> Vec<KeyCandidate> {
+    let basename: &str = filename.split('.').next().unwrap_or(filename);
+    let name_key = file_name_encryption_key(filename);
+    let mut blk_keys: Vec<(&str, u64, bool, bool)> = Vec::new();
+    for data in version_keys {
+        if let Some((key, encrypted_audio)) = data.find_video(basename) {
+            blk_keys.push((&data.version, key, encrypted_audio, true));
+        }
+    }
+    for data in version_keys {
+        let entry_audio = data.enc_audio.unwrap_or(true);
+        if let Some(key) = data.key {
+            blk_keys.push((&data.version, key, entry_audio, false));
+        }
+        for group in &data.video_groups {
+            blk_keys.push((&data.version, group.key, group.enc_audio.unwrap_or(entry_audio), false));
+        }
+    }
+
+    let mut res: Vec<KeyCandidate> = Vec::new();
+    let candidates = blk_keys.into_iter()
+        .map(|(version, blk_key, encrypted_audio, listed)| (Some(version.to_string()), blk_key, encrypted_audio, listed))
+        .chain(std::iter::once((None, 0, true, false)));
+    for (version, blk_key, encrypted_audio, listed) in candidates {
+        let (key2, key1) = split_key(combine_keys(name_key, blk_key));
+        if !res.iter().any(|candidate| (candidate.key2, candidate.key1) == (key2, key1)) {
+            res.push(KeyCandidate { version, blk_key, key2, key1, encrypted_audio, listed });
+        }
+    }
+    res
+}
+
+/// The `blk` key of a file encrypted with `(key2, key1)`, as found in the key file.
+///
+/// Only the 7 lower bytes of keys are used, so the `blk` key is given modulo 2<sup>56</sup>
+#[must_use]
+pub fn blk_key(filename: &str, key2: u32, key1: u32) -> u64 {
+    (u64::from(key2) << 32 | u64::from(key1)).wrapping_sub(file_name_encryption_key(filename)) & 0xFF_FFFF_FFFF_FFFF
 }
 
 fn blk_encryption_key(filename: &str, version_keys: &[Data]) -> GICSResult<u64>
@@ -80,11 +247,11 @@ fn blk_encryption_key(filename: &str, version_keys: &[Data]) -> GICSResult<u64>
     let basename: &str = filename.split('.').next().unwrap();
     // Look into the version_keys
     for data in version_keys {
-        if data.contains_video(basename) {
-            return Ok(data.get_key())
+        if let Some((key, _)) = data.find_video(basename) {
+            return Ok(key)
         }
     }
-    Err(GICSError::new(&format!("Unable to find decryption key for \"{}\" in version keys file", basename)))
+    Err(GICSError::new(&format!("Unable to find decryption key for \"{basename}\" in version keys file")))
 }
 
 // The intro cutscenes are not exactly encrypted, because the game needs to be able to play